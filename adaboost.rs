@@ -0,0 +1,68 @@
+// SAMME AdaBoost: a sequence of depth-limited trees from build_tree, each
+// grown on the training rows reweighted towards the ones the trees so far got
+// wrong, voting with a weight that grows with their accuracy. row weights are
+// kept scaled to an average of 1 so min_samples_split and min_samples_leaf
+// still read as row counts.
+use crate::{DecisionTreeNode, RoomInfo, TreeConfig, build_tree, majority_class, rescale_weights, search_tree};
+
+pub struct AdaBoost {
+    // (say, tree) per round
+    trees: Vec<(f64,Vec<DecisionTreeNode>)>,
+    classes: usize,
+    // why boosting ended before the requested rounds, if it did
+    pub stopped: Option<String>,
+}
+
+pub fn adaboost(train_vec: &Vec<RoomInfo>, config: &TreeConfig, rounds: usize) -> AdaBoost {
+    let mut booster = AdaBoost { trees: Vec::new(), classes: config.labels.len(), stopped: None };
+    let mut rows:Vec<RoomInfo> = train_vec.clone();
+    rescale_weights(&mut rows);
+    // SAMME counts only the classes that occur in the training rows
+    let mut present:Vec<bool> = vec![false;config.labels.len()];
+    for roominfo in &rows {
+        if let Some(price) = roominfo.price { present[price] = true; }
+    }
+    let k = present.iter().filter(|p| **p).count().max(2) as f64;
+
+    for round in 1..=rounds {
+        let tree_vec = build_tree(rows.clone(), config);
+        let wrong:Vec<bool> = rows.iter()
+            .map(|r| r.price.is_some() && search_tree(&tree_vec, tree_vec.len()-1, r).map(|p| p.price) != r.price).collect();
+        let total:f64 = rows.iter().map(|r| r.weight).sum();
+        let error = rows.iter().zip(&wrong).filter(|(_,w)| **w).map(|(r,_)| r.weight).sum::<f64>() / total;
+        if error >= 1.0 - 1.0 / k {
+            booster.stopped = Some(format!("round {} was no better than chance (error {:.4})", round, error));
+            break;
+        }
+        // a perfect tree gets a large but finite say and ends boosting
+        let error = error.max(1e-10);
+        let say = ((1.0 - error) / error).ln() + (k - 1.0).ln();
+        booster.trees.push((say,tree_vec));
+        if error <= 1e-10 {
+            booster.stopped = Some(format!("round {} fitted the training rows exactly", round));
+            break;
+        }
+        for (roominfo,w) in rows.iter_mut().zip(&wrong) {
+            if *w { roominfo.weight *= say.exp(); }
+        }
+        rescale_weights(&mut rows);
+    }
+    return booster;
+}
+
+impl AdaBoost {
+    pub fn rounds(&self) -> usize {
+        self.trees.len()
+    }
+
+    // the class with the largest total say among the trees voting for it
+    pub fn predict(&self, roominfo: &RoomInfo) -> Option<usize> {
+        let mut votes:Vec<f64> = vec![0.0;self.classes];
+        for (say,tree_vec) in &self.trees {
+            if let Some(prediction) = search_tree(tree_vec, tree_vec.len()-1, roominfo) {
+                votes[prediction.price] += say;
+            }
+        }
+        return majority_class(&votes);
+    }
+}
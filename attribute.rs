@@ -0,0 +1,82 @@
+// features the tree can split on. every attribute sends a listing down one of
+// `num_values()` branches, so adding a categorical feature is one impl here plus
+// an entry in `split_attributes`, and a numeric one is an entry in `numeric_features`.
+use std::fmt;
+use std::rc::Rc;
+use crate::RoomInfo;
+
+pub trait Attribute: fmt::Debug {
+    fn name(&self) -> String;
+    fn num_values(&self) -> usize;
+    // branch index in 0..num_values(), None when the listing's value is missing
+    fn value(&self, roominfo: &RoomInfo) -> Option<usize>;
+}
+
+#[derive(Debug)]
+pub struct RoomTypeAttribute;
+
+impl Attribute for RoomTypeAttribute {
+    fn name(&self) -> String { String::from("RoomType") }
+    fn num_values(&self) -> usize { 4 }
+    fn value(&self, roominfo: &RoomInfo) -> Option<usize> { roominfo.room_type.map(|t| t as usize) }
+}
+
+// a raw numeric column; the builder picks a `<= threshold` cut for it at every node
+#[derive(Clone,Copy)]
+pub struct NumericFeature {
+    pub name: &'static str,
+    pub get: fn(&RoomInfo) -> Option<f64>,
+}
+
+impl fmt::Debug for NumericFeature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+pub fn numeric_features() -> Vec<NumericFeature> {
+    vec![
+        NumericFeature { name: "bedrooms", get: |r| r.bedrooms },
+        NumericFeature { name: "number_of_reviews", get: |r| r.number_of_reviews },
+        NumericFeature { name: "review_scores_value", get: |r| r.review_score },
+        NumericFeature { name: "amenities_count", get: |r| r.amenities.as_ref().map(|a| a.len() as f64) },
+    ]
+}
+
+// `<= threshold` (0) or `> threshold` (1)
+#[derive(Debug)]
+pub struct ThresholdAttribute {
+    pub feature: NumericFeature,
+    pub threshold: f64,
+}
+
+impl Attribute for ThresholdAttribute {
+    fn name(&self) -> String { format!("{}<={}", self.feature.name, self.threshold) }
+    fn num_values(&self) -> usize { 2 }
+    fn value(&self, roominfo: &RoomInfo) -> Option<usize> {
+        (self.feature.get)(roominfo).map(|v| if v <= self.threshold { 0 } else { 1 })
+    }
+}
+
+// has (1) or has not (0) the named amenity
+#[derive(Debug)]
+pub struct AmenityAttribute {
+    pub amenity: String,
+}
+
+impl Attribute for AmenityAttribute {
+    fn name(&self) -> String { format!("Amenity:{}", self.amenity) }
+    fn num_values(&self) -> usize { 2 }
+    fn value(&self, roominfo: &RoomInfo) -> Option<usize> {
+        roominfo.amenities.as_ref().map(|a| if a.contains(&self.amenity) { 1 } else { 0 })
+    }
+}
+
+// every attribute the builder may choose from, with one AmenityAttribute per name in `amenities`
+pub fn split_attributes(amenities: &Vec<String>) -> Vec<Rc<dyn Attribute>> {
+    let mut attributes:Vec<Rc<dyn Attribute>> = vec![Rc::new(RoomTypeAttribute)];
+    for amenity in amenities {
+        attributes.push(Rc::new(AmenityAttribute { amenity: amenity.clone() }));
+    }
+    return attributes;
+}
@@ -0,0 +1,156 @@
+// rare price ranges such as Above500 hold a handful of listings, so a tree
+// fitted to every listing alike rarely predicts them. two remedies, applied to
+// train_vec once its classes are known: weigh the classes, or resample them.
+use crate::{RoomInfo, class_dist};
+use crate::rng::Rng;
+
+#[derive(Debug,Clone)]
+pub enum ClassWeights {
+    // every class present carries the same total weight
+    Balanced,
+    // (price range label, weight); classes not listed keep a weight of 1
+    Custom(Vec<(String,f64)>),
+}
+
+impl ClassWeights {
+    // "balanced" or "<label>:<weight>,..." such as "Above500:5,_400_500:2"
+    pub fn from_spec(spec: &str) -> Result<ClassWeights,String> {
+        if spec == "balanced" { return Ok(ClassWeights::Balanced); }
+        let mut weights:Vec<(String,f64)> = Vec::new();
+        for part in spec.split(",") {
+            let (label,weight) = match part.rsplit_once(":") {
+                Some(pair) => pair,
+                None => return Err(format!("expected balanced or <range>:<weight>, got {:?}", part))
+            };
+            match weight.parse::<f64>() {
+                Ok(w) if w.is_finite() && w >= 0.0 => weights.push((String::from(label), w)),
+                _ => return Err(format!("bad weight {:?} for {}", weight, label))
+            }
+        }
+        return Ok(ClassWeights::Custom(weights));
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            ClassWeights::Balanced => String::from("balanced"),
+            ClassWeights::Custom(weights) => weights.iter().map(|(l,w)| format!("{}:{}", l, w)).collect::<Vec<String>>().join(","),
+        }
+    }
+
+    // the weight of every class in `labels`
+    fn resolve(&self, train_vec: &Vec<RoomInfo>, labels: &Vec<String>) -> Result<Vec<f64>,String> {
+        match self {
+            ClassWeights::Balanced => {
+                let totals = class_dist(train_vec, labels.len());
+                let all:f64 = totals.iter().sum();
+                let present = totals.iter().filter(|t| **t > 0.0).count() as f64;
+                Ok(totals.iter().map(|t| if *t > 0.0 { all / (present * t) } else { 1.0 }).collect())
+            },
+            ClassWeights::Custom(weights) => {
+                let mut resolved:Vec<f64> = vec![1.0;labels.len()];
+                for (label,weight) in weights {
+                    match labels.iter().position(|l| l == label) {
+                        Some(class) => resolved[class] = *weight,
+                        None => return Err(format!("no price range {:?}, expected one of {}", label, labels.join(", ")))
+                    }
+                }
+                Ok(resolved)
+            }
+        }
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Resample {
+    // repeat listings of the smaller classes until each matches the largest
+    Over,
+    // drop listings of the larger classes until each matches the smallest
+    Under,
+}
+
+impl Resample {
+    pub fn from_name(name: &str) -> Option<Resample> {
+        match name {
+            "over" => Some(Resample::Over),
+            "under" => Some(Resample::Under),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Resample::Over => "over",
+            Resample::Under => "under",
+        }
+    }
+}
+
+// multiply every listing's weight by the weight of its class
+pub fn weigh_classes(train_vec: &mut Vec<RoomInfo>, weights: &ClassWeights, labels: &Vec<String>) -> Result<(),String> {
+    let resolved = weights.resolve(train_vec, labels)?;
+    for roominfo in train_vec {
+        if let Some(price) = roominfo.price { roominfo.weight *= resolved[price]; }
+    }
+    return Ok(());
+}
+
+// resample the listings of every class present to the same count. listings
+// without a class are kept as they are; oversampled copies are drawn with
+// replacement and follow the originals, with `copy_of` pointing back at them.
+pub fn resample(train_vec: Vec<RoomInfo>, method: Resample, classes: usize, seed: u64) -> Vec<RoomInfo> {
+    let mut rng = Rng::new(seed);
+    let mut members:Vec<Vec<usize>> = vec![Vec::new();classes];
+    for (i,roominfo) in train_vec.iter().enumerate() {
+        if let Some(price) = roominfo.price { members[price].push(i); }
+    }
+    let sizes = members.iter().map(|m| m.len()).filter(|n| *n > 0);
+    let target = match method {
+        Resample::Over => sizes.max().unwrap_or(0),
+        Resample::Under => sizes.min().unwrap_or(0),
+    };
+
+    let mut keep:Vec<bool> = vec![true;train_vec.len()];
+    let mut extra:Vec<usize> = Vec::new();
+    for indices in &mut members {
+        if indices.is_empty() { continue; }
+        match method {
+            Resample::Over => {
+                for _ in indices.len()..target { extra.push(indices[rng.below(indices.len())]); }
+            },
+            Resample::Under => {
+                rng.shuffle(indices);
+                for i in &indices[target..] { keep[*i] = false; }
+            }
+        }
+    }
+    let mut resampled:Vec<RoomInfo> = Vec::new();
+    let mut position:Vec<usize> = vec![0;train_vec.len()];
+    for (i,(roominfo,k)) in train_vec.iter().zip(&keep).enumerate() {
+        position[i] = resampled.len();
+        if *k { resampled.push(roominfo.clone()); }
+    }
+    for i in extra {
+        let mut copy = train_vec[i].clone();
+        copy.copy_of = Some(position[i]);
+        resampled.push(copy);
+    }
+    return resampled;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::fitted;
+
+    #[test]
+    fn copies_point_at_their_originals() {
+        let (train_vec,config) = fitted(24);
+        let resampled = resample(train_vec.clone(), Resample::Over, config.labels.len(), 7);
+        assert!(resampled.len() > train_vec.len());
+        for roominfo in &resampled[train_vec.len()..] {
+            let original = &resampled[roominfo.copy_of.unwrap()];
+            assert!(original.copy_of.is_none());
+            assert_eq!(original.price_cents, roominfo.price_cents);
+        }
+    }
+}
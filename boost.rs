@@ -0,0 +1,297 @@
+// gradient boosting with shallow regression trees. for the price every tree
+// is fitted to the residuals of the model so far, and its leaves hold their
+// mean. for the price classes one tree per class and round is fitted to the
+// gradient r of the softmax loss, and its leaves are reset to Friedman's
+// one-step Newton value (K-1)/K * sum(r) / sum(|r|(1-|r|)) over the rows they
+// hold; the mean gradient alone makes steps far too small as the probabilities
+// approach 0 or 1. every step is scaled by the learning rate. rounds stop once
+// the loss on stop_vec, rows held out of the training rows, has not improved
+// for `patience` rounds, and the model keeps the rounds up to its best loss
+// there. with no stop_vec rows or a patience of 0 every round is kept.
+use crate::{DecisionTreeNode, RoomInfo, TreeConfig, majority_class};
+use crate::regression::{Loss, build_regression_tree, predict_price, price};
+use crate::rng::Rng;
+
+pub struct BoostSettings {
+    pub rounds: usize,
+    pub learning_rate: f64,
+    // share of train_vec drawn, without replacement, for every round
+    pub subsample: f64,
+    // 0 never stops early
+    pub patience: usize,
+    pub seed: u64,
+}
+
+pub struct PriceBooster {
+    base: f64,
+    learning_rate: f64,
+    trees: Vec<Vec<DecisionTreeNode>>,
+    rounds_run: usize,
+    // best loss on stop_vec, None when rounds were not stopped early
+    stop_loss: Option<f64>,
+}
+
+pub struct ClassBooster {
+    // log prior of every class
+    base: Vec<f64>,
+    learning_rate: f64,
+    // one tree per class for every round
+    trees: Vec<Vec<Vec<DecisionTreeNode>>>,
+    rounds_run: usize,
+    stop_loss: Option<f64>,
+}
+
+// indices of the train_vec rows drawn for one round, in file order
+fn round_sample(train_vec: &Vec<RoomInfo>, subsample: f64, rng: &mut Rng) -> Vec<usize> {
+    let mut indices:Vec<usize> = (0..train_vec.len()).collect();
+    rng.shuffle(&mut indices);
+    indices.truncate(((subsample * train_vec.len() as f64).round() as usize).max(1));
+    indices.sort();
+    return indices;
+}
+
+// a regression tree on copies of the drawn rows with `target` set
+fn fit_tree(train_vec: &Vec<RoomInfo>, indices: &Vec<usize>, targets: &Vec<f64>, config: &TreeConfig) -> Vec<DecisionTreeNode> {
+    let mut sample:Vec<RoomInfo> = Vec::new();
+    for i in indices {
+        let mut roominfo = train_vec[*i].clone();
+        roominfo.target = Some(targets[*i]);
+        sample.push(roominfo);
+    }
+    return build_regression_tree(sample, config, &Loss::Squared);
+}
+
+fn tree_output(tree_vec: &Vec<DecisionTreeNode>, roominfo: &RoomInfo) -> f64 {
+    predict_price(tree_vec, tree_vec.len()-1, roominfo)
+}
+
+// whether to stop after this round, updating the best round seen so far
+fn early_stop(loss: f64, round: usize, best: &mut (f64,usize), patience: usize) -> bool {
+    if loss < best.0 - 1e-12 {
+        *best = (loss,round);
+        return false;
+    }
+    return round - best.1 >= patience;
+}
+
+// the loss on an empty stop_vec says nothing, so it cannot pick a round
+fn stops_early(stop_vec: &Vec<RoomInfo>, settings: &BoostSettings) -> bool {
+    settings.patience > 0 && !stop_vec.is_empty()
+}
+
+pub fn boost_price(train_vec: &Vec<RoomInfo>, stop_vec: &Vec<RoomInfo>, config: &TreeConfig, settings: &BoostSettings) -> PriceBooster {
+    let mut rng = Rng::new(settings.seed);
+    let prices:Vec<f64> = train_vec.iter().map(price).collect();
+    let weight:f64 = train_vec.iter().map(|r| r.weight).sum();
+    let base = if weight == 0.0 { 0.0 } else { train_vec.iter().map(|r| r.weight * price(r)).sum::<f64>() / weight };
+    let mut booster = PriceBooster { base, learning_rate: settings.learning_rate, trees: Vec::new(), rounds_run: 0, stop_loss: None };
+    let tracking = stops_early(stop_vec, settings);
+
+    let mut current:Vec<f64> = vec![base;train_vec.len()];
+    let mut stop_current:Vec<f64> = vec![base;stop_vec.len()];
+    let mut best = (rmse(stop_vec, &stop_current),0);
+    for round in 1..=settings.rounds {
+        let residuals:Vec<f64> = prices.iter().zip(&current).map(|(p,c)| p - c).collect();
+        let tree_vec = fit_tree(train_vec, &round_sample(train_vec, settings.subsample, &mut rng), &residuals, config);
+        for (i,roominfo) in train_vec.iter().enumerate() { current[i] += settings.learning_rate * tree_output(&tree_vec, roominfo); }
+        for (i,roominfo) in stop_vec.iter().enumerate() { stop_current[i] += settings.learning_rate * tree_output(&tree_vec, roominfo); }
+        booster.trees.push(tree_vec);
+        booster.rounds_run = round;
+        if tracking && early_stop(rmse(stop_vec, &stop_current), round, &mut best, settings.patience) { break; }
+    }
+    if tracking {
+        booster.trees.truncate(best.1);
+        booster.stop_loss = Some(best.0);
+    }
+    return booster;
+}
+
+fn rmse(stop_vec: &Vec<RoomInfo>, predicted: &Vec<f64>) -> f64 {
+    let weight:f64 = stop_vec.iter().map(|r| r.weight).sum();
+    if weight == 0.0 { return 0.0; }
+    let squared:f64 = stop_vec.iter().zip(predicted).map(|(r,p)| r.weight * (price(r) - p) * (price(r) - p)).sum();
+    return (squared / weight).sqrt();
+}
+
+impl PriceBooster {
+    pub fn predict(&self, roominfo: &RoomInfo) -> f64 {
+        self.base + self.trees.iter().map(|t| self.learning_rate * tree_output(t, roominfo)).sum::<f64>()
+    }
+
+    pub fn print_summary(&self, settings: &BoostSettings) {
+        let stopping = match self.stop_loss {
+            Some(loss) => format!("early-stopping RMSE ${:.2}", loss),
+            None => String::from("no early stopping"),
+        };
+        println!("Boosted price: kept {} of {} rounds run ({}), learning_rate={}, subsample={}, seed={}",
+            self.trees.len(), self.rounds_run, stopping, settings.learning_rate, settings.subsample, settings.seed);
+        println!();
+    }
+}
+
+fn softmax(scores: &Vec<f64>) -> Vec<f64> {
+    let max = scores.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let exps:Vec<f64> = scores.iter().map(|s| (s - max).exp()).collect();
+    let sum:f64 = exps.iter().sum();
+    return exps.iter().map(|e| e / sum).collect();
+}
+
+pub fn boost_classes(train_vec: &Vec<RoomInfo>, stop_vec: &Vec<RoomInfo>, config: &TreeConfig, settings: &BoostSettings) -> ClassBooster {
+    let mut rng = Rng::new(settings.seed);
+    let classes = config.labels.len();
+    // add-one smoothing keeps classes missing from train_vec finite
+    let mut counts:Vec<f64> = vec![1.0;classes];
+    for roominfo in train_vec {
+        if let Some(price) = roominfo.price { counts[price] += roominfo.weight; }
+    }
+    let total:f64 = counts.iter().sum();
+    let base:Vec<f64> = counts.iter().map(|c| (c / total).ln()).collect();
+    let mut booster = ClassBooster { base: base.clone(), learning_rate: settings.learning_rate, trees: Vec::new(), rounds_run: 0, stop_loss: None };
+    let tracking = stops_early(stop_vec, settings);
+
+    let mut current:Vec<Vec<f64>> = vec![base.clone();train_vec.len()];
+    let mut stop_current:Vec<Vec<f64>> = vec![base.clone();stop_vec.len()];
+    let mut best = (log_loss(stop_vec, &stop_current),0);
+    for round in 1..=settings.rounds {
+        let indices = round_sample(train_vec, settings.subsample, &mut rng);
+        let probabilities:Vec<Vec<f64>> = current.iter().map(softmax).collect();
+        let mut round_trees:Vec<Vec<DecisionTreeNode>> = Vec::new();
+        for k in 0..classes {
+            // the negative gradient of the cross-entropy for class k
+            let gradients:Vec<f64> = train_vec.iter().zip(&probabilities)
+                .map(|(r,p)| if r.price == Some(k) { 1.0 - p[k] } else { -p[k] }).collect();
+            let mut tree_vec = fit_tree(train_vec, &indices, &gradients, config);
+            newton_leaves(&mut tree_vec, train_vec, &indices, &gradients, classes);
+            round_trees.push(tree_vec);
+        }
+        for (k,tree_vec) in round_trees.iter().enumerate() {
+            for (i,roominfo) in train_vec.iter().enumerate() { current[i][k] += settings.learning_rate * tree_output(tree_vec, roominfo); }
+            for (i,roominfo) in stop_vec.iter().enumerate() { stop_current[i][k] += settings.learning_rate * tree_output(tree_vec, roominfo); }
+        }
+        booster.trees.push(round_trees);
+        booster.rounds_run = round;
+        if tracking && early_stop(log_loss(stop_vec, &stop_current), round, &mut best, settings.patience) { break; }
+    }
+    if tracking {
+        booster.trees.truncate(best.1);
+        booster.stop_loss = Some(best.0);
+    }
+    return booster;
+}
+
+// set every leaf to (K-1)/K * sum(w r) / sum(w |r|(1-|r|)) over the drawn rows
+// that reach it; a leaf without any keeps no say
+fn newton_leaves(tree_vec: &mut Vec<DecisionTreeNode>, train_vec: &Vec<RoomInfo>, indices: &Vec<usize>, gradients: &Vec<f64>, classes: usize) {
+    let mut sums:Vec<(f64,f64)> = vec![(0.0,0.0);tree_vec.len()];
+    for i in indices {
+        let (r,w) = (gradients[*i],train_vec[*i].weight);
+        let mut shares:Vec<(usize,f64)> = Vec::new();
+        leaf_shares(tree_vec, tree_vec.len()-1, &train_vec[*i], 1.0, &mut shares);
+        for (leaf,share) in shares {
+            sums[leaf].0 += share * w * r;
+            sums[leaf].1 += share * w * r.abs() * (1.0 - r.abs());
+        }
+    }
+    let scale = (classes as f64 - 1.0) / classes as f64;
+    for (node,(numerator,denominator)) in tree_vec.iter_mut().zip(sums) {
+        if node.split.is_some() { continue; }
+        node.value = Some(if denominator < 1e-12 { 0.0 } else { scale * numerator / denominator });
+    }
+}
+
+// the leaves a row reaches with the share of it each gets: all of it down its
+// own branch, or split by training weight when the split value is missing
+fn leaf_shares(tree_vec: &Vec<DecisionTreeNode>, id: usize, roominfo: &RoomInfo, share: f64, shares: &mut Vec<(usize,f64)>) {
+    let node = &tree_vec[id];
+    let attribute = match &node.split {
+        Some(attribute) => attribute,
+        None => { shares.push((id,share)); return; }
+    };
+    match attribute.value(roominfo) {
+        Some(value) => leaf_shares(tree_vec, node.children[value], roominfo, share, shares),
+        None => for child in &node.children {
+            let part = tree_vec[*child].weight / node.weight;
+            if part > 0.0 { leaf_shares(tree_vec, *child, roominfo, share * part, shares); }
+        }
+    }
+}
+
+// mean cross-entropy of the true classes
+fn log_loss(stop_vec: &Vec<RoomInfo>, scores: &Vec<Vec<f64>>) -> f64 {
+    let (mut loss,mut count) = (0.0,0.0);
+    for (roominfo,score) in stop_vec.iter().zip(scores) {
+        if let Some(price) = roominfo.price {
+            loss -= roominfo.weight * softmax(score)[price].max(1e-15).ln();
+            count += roominfo.weight;
+        }
+    }
+    if count == 0.0 { return 0.0; }
+    return loss / count;
+}
+
+impl ClassBooster {
+    pub fn predict(&self, roominfo: &RoomInfo) -> Option<usize> {
+        let mut scores = self.base.clone();
+        for round_trees in &self.trees {
+            for (k,tree_vec) in round_trees.iter().enumerate() {
+                scores[k] += self.learning_rate * tree_output(tree_vec, roominfo);
+            }
+        }
+        return majority_class(&softmax(&scores));
+    }
+
+    pub fn print_summary(&self, settings: &BoostSettings) {
+        let stopping = match self.stop_loss {
+            Some(loss) => format!("early-stopping log loss {:.4}", loss),
+            None => String::from("no early stopping"),
+        };
+        println!("Boosted classes: kept {} of {} rounds run ({}), learning_rate={}, subsample={}, seed={}",
+            self.trees.len(), self.rounds_run, stopping, settings.learning_rate, settings.subsample, settings.seed);
+        println!();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::fitted;
+
+    fn settings(patience: usize) -> BoostSettings {
+        BoostSettings { rounds: 8, learning_rate: 0.1, subsample: 1.0, patience, seed: 1 }
+    }
+
+    #[test]
+    fn keeps_every_round_without_a_stop_set() {
+        let (train_vec,config) = fitted(24);
+        let booster = boost_price(&train_vec, &Vec::new(), &config, &settings(2));
+        assert_eq!((booster.trees.len(),booster.stop_loss), (8,None));
+        let booster = boost_classes(&train_vec, &Vec::new(), &config, &settings(2));
+        assert_eq!((booster.trees.len(),booster.stop_loss), (8,None));
+    }
+
+    #[test]
+    fn keeps_every_round_with_no_patience() {
+        let (train_vec,config) = fitted(24);
+        let stop_vec:Vec<RoomInfo> = train_vec[..6].to_vec();
+        let booster = boost_price(&train_vec, &stop_vec, &config, &settings(0));
+        assert_eq!(booster.trees.len(), 8);
+        let booster = boost_classes(&train_vec, &stop_vec, &config, &settings(0));
+        assert_eq!(booster.trees.len(), 8);
+    }
+
+    #[test]
+    fn class_leaves_take_a_newton_step() {
+        let (train_vec,config) = fitted(24);
+        let config = TreeConfig { max_depth: Some(0), ..config };
+        let booster = boost_classes(&train_vec, &Vec::new(), &config, &BoostSettings { rounds: 1, ..settings(0) });
+        // a single leaf holds every row, all starting from the prior p
+        let classes = config.labels.len() as f64;
+        let total:f64 = train_vec.iter().map(|r| r.weight).sum();
+        for (k,tree_vec) in booster.trees[0].iter().enumerate() {
+            let p = booster.base[k].exp();
+            let of_class:f64 = train_vec.iter().filter(|r| r.price == Some(k)).map(|r| r.weight).sum();
+            let expected = (classes - 1.0) / classes * (of_class - total * p) / (total * p * (1.0 - p));
+            assert!((tree_vec[0].value.unwrap() - expected).abs() < 1e-9, "class {}", k);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+// split criteria for classification trees. every function takes the
+// contingency table dist[value][class] of a candidate split and returns a
+// score where higher is better and anything <= 0 is not worth splitting on.
+// rows whose value is missing are left out of dist and passed separately as
+// `missing`, their class counts.
+use crate::{attribute_entropy, info_entropy};
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Criterion {
+    InfoGain,
+    GainRatio,
+    Gini,
+    ChiSquare,
+}
+
+impl Criterion {
+    pub fn from_name(name: &str) -> Option<Criterion> {
+        match name {
+            "gain" => Some(Criterion::InfoGain),
+            "ratio" => Some(Criterion::GainRatio),
+            "gini" => Some(Criterion::Gini),
+            "chi2" => Some(Criterion::ChiSquare),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Criterion::InfoGain => "gain",
+            Criterion::GainRatio => "ratio",
+            Criterion::Gini => "gini",
+            Criterion::ChiSquare => "chi2",
+        }
+    }
+
+    // score used to pick between candidate attributes. as in C4.5 the gain is
+    // measured on the known rows and scaled by their share.
+    pub fn score(&self, dist: &Vec<Vec<f64>>, missing: &Vec<f64>) -> f64 {
+        match self {
+            Criterion::InfoGain => known_share(dist, missing) * info_gain(dist),
+            Criterion::GainRatio => gain_ratio(dist, missing),
+            Criterion::Gini => known_share(dist, missing) * gini_decrease(dist),
+            Criterion::ChiSquare => chi_square_z(dist),
+        }
+    }
+
+    // the impurity drop checked against min_impurity_decrease: Gini for the
+    // Gini criterion, entropy for the others
+    pub fn impurity_decrease(&self, dist: &Vec<Vec<f64>>, missing: &Vec<f64>) -> f64 {
+        match self {
+            Criterion::Gini => known_share(dist, missing) * gini_decrease(dist),
+            _ => known_share(dist, missing) * info_gain(dist),
+        }
+    }
+
+    // score used to place a numeric threshold. every cut is binary, so C4.5
+    // ranks them by plain gain and chi-square needs no degrees of freedom fix.
+    pub fn threshold_score(&self, dist: &Vec<Vec<f64>>) -> f64 {
+        match self {
+            Criterion::InfoGain | Criterion::GainRatio => info_gain(dist),
+            Criterion::Gini => gini_decrease(dist),
+            Criterion::ChiSquare => chi_square(dist),
+        }
+    }
+}
+
+fn class_totals(dist: &Vec<Vec<f64>>) -> Vec<f64> {
+    let mut totals:Vec<f64> = vec![0.0;dist.first().map_or(0, |d| d.len())];
+    for row in dist {
+        for (k,count) in row.iter().enumerate() { totals[k] += count; }
+    }
+    return totals;
+}
+
+// fraction of the rows whose value is known
+pub fn known_share(dist: &Vec<Vec<f64>>, missing: &Vec<f64>) -> f64 {
+    let known:f64 = dist.iter().map(|row| row.iter().sum::<f64>()).sum();
+    let all = known + missing.iter().sum::<f64>();
+    if all == 0.0 { return 0.0; }
+    return known / all;
+}
+
+pub fn info_gain(dist: &Vec<Vec<f64>>) -> f64 {
+    let totals = class_totals(dist);
+    if totals.iter().sum::<f64>() == 0.0 { return 0.0; }
+    let (attr_ent,_) = attribute_entropy(dist);
+    return info_entropy(&totals) - attr_ent;
+}
+
+// the missing rows count as one more branch in the split information. an
+// attribute with a single observed value has no split information; it scores
+// 0 rather than the 0/0 NaN of the plain ratio.
+pub fn gain_ratio(dist: &Vec<Vec<f64>>, missing: &Vec<f64>) -> f64 {
+    let gain = known_share(dist, missing) * info_gain(dist);
+    let mut branches = dist.clone();
+    branches.push(missing.clone());
+    let (_,split_info) = attribute_entropy(&branches);
+    if split_info <= 0.0 || !split_info.is_finite() { return 0.0; }
+    return gain / split_info;
+}
+
+fn gini(counts: &Vec<f64>) -> f64 {
+    let sum:f64 = counts.iter().sum();
+    if sum == 0.0 { return 0.0; }
+    return 1.0 - counts.iter().map(|c| (c / sum) * (c / sum)).sum::<f64>();
+}
+
+pub fn gini_decrease(dist: &Vec<Vec<f64>>) -> f64 {
+    let totals = class_totals(dist);
+    let all:f64 = totals.iter().sum();
+    if all == 0.0 { return 0.0; }
+    let mut children:f64 = 0.0;
+    for row in dist {
+        children += row.iter().sum::<f64>() / all * gini(row);
+    }
+    return gini(&totals) - children;
+}
+
+// Pearson's statistic over the cells whose expected count is non-zero
+pub fn chi_square(dist: &Vec<Vec<f64>>) -> f64 {
+    let totals = class_totals(dist);
+    let all:f64 = totals.iter().sum();
+    if all == 0.0 { return 0.0; }
+    let mut statistic:f64 = 0.0;
+    for row in dist {
+        let row_sum:f64 = row.iter().sum();
+        for (k,observed) in row.iter().enumerate() {
+            let expected = row_sum * totals[k] / all;
+            if expected > 0.0 { statistic += (observed - expected) * (observed - expected) / expected; }
+        }
+    }
+    return statistic;
+}
+
+// the statistic turned into an approximate standard normal score with the
+// Wilson-Hilferty transform, so attributes with different numbers of values
+// compare fairly. a split scores above 0 once it is more dependent on the
+// price class than chance would suggest.
+pub fn chi_square_z(dist: &Vec<Vec<f64>>) -> f64 {
+    let rows = dist.iter().filter(|r| r.iter().sum::<f64>() > 0.0).count();
+    let columns = class_totals(dist).iter().filter(|t| **t > 0.0).count();
+    if rows < 2 || columns < 2 { return 0.0; }
+    let df = ((rows - 1) * (columns - 1)) as f64;
+    let k = 2.0 / (9.0 * df);
+    return ((chi_square(dist) / df).cbrt() - (1.0 - k)) / k.sqrt();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an attribute every known row shares one value of has no split
+    // information, which must not turn into a NaN score
+    #[test]
+    fn single_value_scores_zero() {
+        let dist:Vec<Vec<f64>> = vec![vec![3.0,2.0,0.0], vec![0.0,0.0,0.0]];
+        let single:Vec<Vec<f64>> = vec![vec![3.0,2.0,0.0]];
+        for criterion in [Criterion::InfoGain, Criterion::GainRatio, Criterion::Gini, Criterion::ChiSquare] {
+            for missing in [vec![0.0,0.0,0.0], vec![1.0,0.0,1.0]] {
+                for d in [&dist, &single] {
+                    let score = criterion.score(d, &missing);
+                    assert_eq!(score, 0.0, "{:?} {:?} {:?}", criterion, d, missing);
+                    assert_eq!(criterion.impurity_decrease(d, &missing), 0.0);
+                }
+            }
+            assert_eq!(criterion.threshold_score(&dist), 0.0);
+        }
+    }
+
+    #[test]
+    fn informative_split_scores_above_zero() {
+        let dist:Vec<Vec<f64>> = vec![vec![4.0,0.0], vec![0.0,4.0]];
+        for criterion in [Criterion::InfoGain, Criterion::GainRatio, Criterion::Gini, Criterion::ChiSquare] {
+            assert!(criterion.score(&dist, &vec![0.0,0.0]) > 0.0, "{:?}", criterion);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+// minimal RFC 4180 reader: quoted fields, "" escapes, newlines inside quotes,
+// CRLF line endings and a leading UTF-8 BOM.
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+pub struct CsvReader<R> {
+    reader: R,
+    line_num: usize,
+    record_line: usize,
+}
+
+impl CsvReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<CsvReader<BufReader<File>>> {
+        let file = File::open(path)?;
+        Ok(CsvReader::new(BufReader::new(file)))
+    }
+}
+
+impl<R: BufRead> CsvReader<R> {
+    pub fn new(reader: R) -> CsvReader<R> {
+        CsvReader { reader, line_num: 0, record_line: 0 }
+    }
+
+    // physical line (1-based) on which the last returned record started
+    pub fn line(&self) -> usize {
+        self.record_line
+    }
+
+    pub fn read_record(&mut self) -> io::Result<Option<Vec<String>>> {
+        let mut fields: Vec<String> = Vec::new();
+        let mut field = String::new();
+        let mut in_quotes = false;
+        let mut started = false;
+        let mut buf = String::new();
+
+        loop {
+            buf.clear();
+            if self.reader.read_line(&mut buf)? == 0 {
+                if in_quotes {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData,
+                        format!("unterminated quoted field starting on line {}", self.record_line)));
+                }
+                if !started { return Ok(None); }
+                fields.push(field);
+                return Ok(Some(fields));
+            }
+            self.line_num += 1;
+            let mut line: &str = &buf;
+            if self.line_num == 1 {
+                line = line.strip_prefix('\u{feff}').unwrap_or(line);
+            }
+            if !started {
+                // skip blank lines between records
+                if line.trim_end_matches(['\r', '\n']).is_empty() { continue; }
+                started = true;
+                self.record_line = self.line_num;
+            }
+
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                if in_quotes {
+                    if c == '"' {
+                        if chars.peek() == Some(&'"') {
+                            field.push('"');
+                            chars.next();
+                        } else {
+                            in_quotes = false;
+                        }
+                    } else {
+                        field.push(c);
+                    }
+                    continue;
+                }
+                match c {
+                    ',' => fields.push(std::mem::take(&mut field)),
+                    '"' if field.is_empty() => in_quotes = true,
+                    '\r' if chars.peek() == Some(&'\n') || chars.peek().is_none() => {},
+                    '\n' => {
+                        fields.push(field);
+                        return Ok(Some(fields));
+                    },
+                    _ => field.push(c),
+                }
+            }
+            // the line ended inside a quoted field (newline kept) or without a
+            // trailing newline at EOF; either way keep reading.
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for CsvReader<R> {
+    type Item = io::Result<Vec<String>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<String>>> {
+        self.read_record().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CsvReader;
+    use std::io::Cursor;
+
+    fn records(text: &str) -> Vec<Vec<String>> {
+        CsvReader::new(Cursor::new(text)).map(|r| r.unwrap()).collect()
+    }
+
+    #[test]
+    fn reads_rfc_4180() {
+        let cases:Vec<(&str,Vec<Vec<&str>>)> = vec![
+            ("a,b\n1,2\n", vec![vec!["a","b"], vec!["1","2"]]),
+            ("a,\"b,c\",d\n", vec![vec!["a","b,c","d"]]),
+            ("\"say \"\"hi\"\"\",x\n", vec![vec!["say \"hi\"","x"]]),
+            ("\"two\nlines\",x\ny,z\n", vec![vec!["two\nlines","x"], vec!["y","z"]]),
+            ("a,b\r\n1,2\r\n", vec![vec!["a","b"], vec!["1","2"]]),
+            ("\u{feff}a,b\n1,2", vec![vec!["a","b"], vec!["1","2"]]),
+            ("a,b\n\n1,\n", vec![vec!["a","b"], vec!["1",""]]),
+            ("", vec![]),
+        ];
+        for (text,expected) in cases {
+            let expected:Vec<Vec<String>> = expected.iter().map(|r| r.iter().map(|f| String::from(*f)).collect()).collect();
+            assert_eq!(records(text), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn reports_record_lines_and_unterminated_quotes() {
+        let mut reader = CsvReader::new(Cursor::new("a\n\"b\nc\"\nd\n"));
+        let mut lines:Vec<usize> = Vec::new();
+        while reader.read_record().unwrap().is_some() { lines.push(reader.line()); }
+        assert_eq!(lines, vec![1,2,4]);
+        assert!(CsvReader::new(Cursor::new("\"open,x\n")).read_record().is_err());
+    }
+}
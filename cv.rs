@@ -0,0 +1,123 @@
+// k-fold cross-validation: every listing is scored once per repeat by a tree
+// grown on the other folds. folds are stratified by price class, and each
+// repeat reshuffles them with the next seed.
+use crate::{RoomInfo, Settings, build_tree, evaluate, fit, prune_tree};
+use crate::rng::Rng;
+use crate::split::{Split, price_strata, split};
+
+// how to cross-validate: `ratio` is the share of the training folds that
+// pruning which needs held-out rows takes for itself
+pub struct CvPlan {
+    pub folds: usize,
+    pub repeats: usize,
+    pub seed: u64,
+    pub ratio: f64,
+}
+
+// one score per fold and repeat
+pub struct CvResult {
+    folds: usize,
+    repeats: usize,
+    accuracy: Vec<f64>,
+    macro_f1: Vec<f64>,
+    // (price range, recall) for every range a fold held; fitted ranges can
+    // differ between folds, so they are matched by label. `labels` lists every
+    // range seen, in price order within each fold.
+    labels: Vec<String>,
+    recall: Vec<(String,f64)>,
+}
+
+impl CvResult {
+    // (mean, standard deviation)
+    pub fn accuracy(&self) -> (f64,f64) {
+        mean_std(&self.accuracy)
+    }
+
+    pub fn macro_f1(&self) -> (f64,f64) {
+        mean_std(&self.macro_f1)
+    }
+
+    pub fn print(&self) {
+        println!("{}-fold cross-validation, {} repeat(s), {} trees", self.folds, self.repeats, self.accuracy.len());
+        let (mean,std) = self.accuracy();
+        println!("Accuracy: {:.4} +/- {:.4}", mean, std);
+        let (mean,std) = self.macro_f1();
+        println!("Macro F1: {:.4} +/- {:.4}", mean, std);
+        println!();
+        println!("Recall by price range:");
+        for label in &self.labels {
+            let scores:Vec<f64> = self.recall.iter().filter(|(l,_)| l == label).map(|(_,r)| *r).collect();
+            if scores.is_empty() { continue; }
+            let (mean,std) = mean_std(&scores);
+            println!("{:>12}{:>11.4} +/- {:.4} over {} folds", label, mean, std, scores.len());
+        }
+    }
+}
+
+// sample standard deviation, 0 for a single score
+fn mean_std(scores: &Vec<f64>) -> (f64,f64) {
+    if scores.is_empty() { return (0.0,0.0); }
+    let n = scores.len() as f64;
+    let mean = scores.iter().sum::<f64>() / n;
+    if scores.len() < 2 { return (mean,0.0); }
+    let variance = scores.iter().map(|s| (s - mean) * (s - mean)).sum::<f64>() / (n - 1.0);
+    return (mean,variance.sqrt());
+}
+
+// grow and score `plan.folds` trees per repeat with `settings`. pruning that
+// needs held-out rows gets them from the training folds, so the scored fold
+// never shapes the tree.
+pub fn cross_validate(listings: &Vec<RoomInfo>, settings: &Settings, plan: &CvPlan) -> Result<CvResult,String> {
+    let (folds,repeats,seed) = (plan.folds,plan.repeats,plan.seed);
+    if folds < 2 || folds > listings.len() {
+        return Err(format!("--folds must be between 2 and the {} priced listings", listings.len()));
+    }
+    if repeats == 0 { return Err(String::from("--repeats must be at least 1")); }
+    let strata = price_strata(listings, &settings.bins).map_err(|e| format!("--bins: {}", e))?;
+    let mut result = CvResult { folds, repeats, accuracy: Vec::new(), macro_f1: Vec::new(), labels: Vec::new(), recall: Vec::new() };
+    for repeat in 0..repeats {
+        let fold_of = assign_folds(&strata, folds, seed.wrapping_add(repeat as u64));
+        for fold in 0..folds {
+            let mut train_vec:Vec<RoomInfo> = Vec::new();
+            let mut test_vec:Vec<RoomInfo> = Vec::new();
+            for (roominfo,f) in listings.iter().zip(&fold_of) {
+                if *f == fold { test_vec.push(roominfo.clone()); } else { train_vec.push(roominfo.clone()); }
+            }
+            let mut verify_vec:Vec<RoomInfo> = Vec::new();
+            if settings.pruning.is_some_and(|p| p.needs_verify()) {
+                // a seed of its own per fold, so every fold holds out different rows
+                let fold_seed = seed.wrapping_add((repeat * folds + fold) as u64);
+                (train_vec,verify_vec) = split(train_vec, &Split::Shuffled, plan.ratio, fold_seed, &Vec::new())?;
+            }
+            let (config,ranges) = fit(settings, &mut train_vec, &mut verify_vec)?;
+            ranges.assign(&mut test_vec);
+            let tree_vec = prune_tree(build_tree(train_vec, &config), &config, &verify_vec);
+            let report = evaluate(&tree_vec, tree_vec.len()-1, &test_vec, &config.labels);
+            result.accuracy.push(report.accuracy());
+            result.macro_f1.push(report.macro_avg().2);
+            for (class,label) in config.labels.iter().enumerate() {
+                if !result.labels.contains(label) { result.labels.push(label.clone()); }
+                if report.support(class) > 0.0 { result.recall.push((label.clone(), report.recall(class))); }
+            }
+        }
+    }
+    return Ok(result);
+}
+
+// fold of every listing: each class is shuffled and dealt round-robin, carrying
+// on from where the previous class stopped so the folds stay the same size
+fn assign_folds(strata: &Vec<usize>, folds: usize, seed: u64) -> Vec<usize> {
+    let mut rng = Rng::new(seed);
+    let mut fold_of:Vec<usize> = vec![0;strata.len()];
+    let mut next:usize = 0;
+    let classes = strata.iter().max().map_or(0, |c| c + 1);
+    for class in 0..classes {
+        let mut indices:Vec<usize> = (0..strata.len()).filter(|i| strata[*i] == class).collect();
+        rng.shuffle(&mut indices);
+        for i in indices {
+            fold_of[i] = next % folds;
+            next += 1;
+        }
+    }
+    return fold_of;
+}
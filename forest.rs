@@ -0,0 +1,101 @@
+// random forests: unpruned trees from make_tree, each grown on a bootstrap
+// sample of train_vec and looking at a random subset of the candidates at
+// every split, combined by voting.
+use std::cell::RefCell;
+use crate::{DecisionTreeNode, Prediction, RoomInfo, TreeConfig, build_tree, majority_class, search_tree};
+use crate::rng::Rng;
+
+#[derive(Debug,Clone,Copy)]
+pub enum Voting {
+    // every tree votes for its predicted class
+    Majority,
+    // the class proportions of the leaves reached are averaged
+    Probability,
+}
+
+pub struct ForestSettings {
+    pub trees: usize,
+    pub max_features: usize,
+    pub voting: Voting,
+    pub seed: u64,
+}
+
+pub struct Forest {
+    trees: Vec<Vec<DecisionTreeNode>>,
+    // in_bag[t][i] is whether row i of train_vec, or an oversampled copy of
+    // it, was drawn for tree t
+    in_bag: Vec<Vec<bool>>,
+    voting: Voting,
+    classes: usize,
+}
+
+pub fn grow_forest(train_vec: &Vec<RoomInfo>, config: &TreeConfig, settings: &ForestSettings) -> Forest {
+    let mut rng = Rng::new(settings.seed);
+    let mut forest = Forest { trees: Vec::new(), in_bag: Vec::new(), voting: settings.voting, classes: config.labels.len() };
+    for _ in 0..settings.trees {
+        let mut in_bag:Vec<bool> = vec![false;train_vec.len()];
+        let mut sample:Vec<RoomInfo> = Vec::new();
+        for _ in 0..train_vec.len() {
+            let i = rng.below(train_vec.len());
+            in_bag[original(train_vec, i)] = true;
+            sample.push(train_vec[i].clone());
+        }
+        let tree_config = TreeConfig {
+            max_features: Some(settings.max_features),
+            rng: RefCell::new(Rng::new(rng.next_u64())),
+            ..config.clone()
+        };
+        forest.trees.push(build_tree(sample, &tree_config));
+        forest.in_bag.push(in_bag);
+    }
+    return forest;
+}
+
+fn original(train_vec: &Vec<RoomInfo>, i: usize) -> usize {
+    train_vec[i].copy_of.unwrap_or(i)
+}
+
+impl Forest {
+    pub fn predict(&self, roominfo: &RoomInfo) -> Option<Prediction> {
+        self.vote(self.trees.iter(), roominfo)
+    }
+
+    // class_dist holds the votes, or the averaged class proportions
+    fn vote<'a, I: Iterator<Item = &'a Vec<DecisionTreeNode>>>(&self, trees: I, roominfo: &RoomInfo) -> Option<Prediction> {
+        let mut class_dist:Vec<f64> = vec![0.0;self.classes];
+        for tree_vec in trees {
+            let prediction = match search_tree(tree_vec, tree_vec.len()-1, roominfo) {
+                Some(prediction) => prediction,
+                None => continue
+            };
+            match self.voting {
+                Voting::Majority => class_dist[prediction.price] += 1.0,
+                Voting::Probability => {
+                    let total:f64 = prediction.class_dist.iter().sum();
+                    if total == 0.0 { class_dist[prediction.price] += 1.0; continue; }
+                    for (k,count) in prediction.class_dist.iter().enumerate() { class_dist[k] += count / total; }
+                }
+            }
+        }
+        let price = majority_class(&class_dist)?;
+        return Some(Prediction { price, class_dist });
+    }
+
+    // accuracy of every training row voted on only by the trees that never saw
+    // it, with the number of rows that had such trees. oversampled copies are
+    // not scored again, and a tree that drew a copy has seen the original.
+    pub fn out_of_bag_accuracy(&self, train_vec: &Vec<RoomInfo>) -> (f64,usize) {
+        let (mut correct,mut weight,mut count) = (0.0,0.0,0);
+        for (i,roominfo) in train_vec.iter().enumerate() {
+            if roominfo.copy_of.is_some() { continue; }
+            let trees = self.trees.iter().zip(&self.in_bag).filter(|(_,in_bag)| !in_bag[i]).map(|(tree_vec,_)| tree_vec);
+            if let Some(prediction) = self.vote(trees, roominfo) {
+                count += 1;
+                weight += roominfo.weight;
+                if Some(prediction.price) == roominfo.price { correct += roominfo.weight; }
+            }
+        }
+        if weight == 0.0 { return (0.0,count); }
+        return (correct / weight, count);
+    }
+}
@@ -1,187 +1,863 @@
 // read the New York listing.csv file and clean it up.
-use std::fs::File;
-use std::io::{self, BufRead};
-use std::path::Path;
+mod adaboost;
+mod attribute;
+mod balance;
+mod boost;
+mod criterion;
+mod csv;
+mod cv;
+mod forest;
+mod price;
+mod prune;
+mod regression;
+mod rng;
+mod search;
+mod split;
 
-#[derive(Debug,PartialEq)]
-enum RoomType {
-    PrivateRoom, EntireHomeApt, HotelRoom
-}
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fmt;
+use std::process;
+use std::rc::Rc;
+use std::str::FromStr;
+use adaboost::adaboost;
+use attribute::{Attribute, NumericFeature, ThresholdAttribute, numeric_features, split_attributes};
+use balance::{ClassWeights, Resample, resample, weigh_classes};
+use boost::{BoostSettings, boost_classes, boost_price};
+use criterion::{Criterion, info_gain, known_share};
+use csv::CsvReader;
+use cv::{CvPlan, cross_validate};
+use forest::{ForestSettings, Voting, grow_forest};
+use price::PriceRanges;
+use prune::{Pruning, cost_complexity_path, print_path, prune};
+use regression::{Loss, build_regression_tree, evaluate_regression, score_regression};
+use rng::Rng;
+use search::{BINS, Search, print_leaderboard, run_search};
+use split::{Split, price_strata, split};
 
-#[derive(Debug,PartialEq)]
-enum BedRooms {
-    One, Two, Three_Five, Over_Six
-}
-
-#[derive(Debug,PartialEq)]
-enum Popularity {
-    Level1, Level2, Level3, Level4, Level5
-}
-
-#[derive(Debug,PartialEq)]
-enum AmenitiesLevel {
-    Few, Common, Abundant, Luxurious
-}
-
-#[derive(Debug,PartialEq)]
-enum PriceRange {
-    Under100, _100_200, _200_300, _300_400, _400_500, Above500
+#[derive(Debug,PartialEq,Clone,Copy)]
+enum RoomType {
+    PrivateRoom, EntireHomeApt, HotelRoom, SharedRoom
 }
 
-#[derive(Debug)]
+#[derive(Debug,Clone)]
 struct RoomInfo
 {
-    room_type: RoomType,
-    bedrooms: BedRooms,
-    popularity: Popularity,
-    amenities_level: AmenitiesLevel,
-    price: PriceRange,
+    // None when the cell is empty
+    room_type: Option<RoomType>,
+    bedrooms: Option<f64>,
+    number_of_reviews: Option<f64>,
+    review_score: Option<f64>,
+    // None when the cell is empty; "[]" is a listing with no amenities
+    amenities: Option<BTreeSet<String>>,
+    // optional column, used to keep a host's listings on one side of a split
+    host_id: Option<String>,
+    price_cents: Option<u64>,
+    // index into PriceRanges, filled in once the ranges are known
+    price: Option<usize>,
+    // fitted by a regression tree instead of the price, e.g. a boosting residual
+    target: Option<f64>,
+    // how much the listing counts in training and evaluation: the optional
+    // weight column (1 when absent), rescaled by fit to an average of 1 over
+    // train_vec, and scaled down to the share a tree node holds once a missing
+    // value has sent the listing down several branches
+    weight: f64,
+    // index in train_vec of the listing this oversampled copy repeats
+    copy_of: Option<usize>,
 }
 
 impl RoomInfo{
     fn new() -> RoomInfo{
         RoomInfo{
-            room_type: RoomType::PrivateRoom,
-            bedrooms: BedRooms::One,
-            popularity: Popularity::Level1,
-            amenities_level: AmenitiesLevel::Few,
-            price: PriceRange::Under100,
+            room_type: None,
+            bedrooms: None,
+            number_of_reviews: None,
+            review_score: None,
+            amenities: None,
+            host_id: None,
+            price_cents: None,
+            price: None,
+            target: None,
+            weight: 1.0,
+            copy_of: None,
         }
     }
 }
 
-#[derive(Debug,Default)]
+#[derive(Debug,Default,Clone)]
 struct DecisionTreeNode {
     attribute: String,
+    // the attribute this node splits on, None for a leaf
+    split: Option<Rc<dyn Attribute>>,
     children: Vec<usize>,
+    // predicted price class of a leaf
+    class: Option<usize>,
+    // predicted nightly price in dollars, regression trees only
+    value: Option<f64>,
+    // number of training rows per price class that reached this node
+    class_dist: Vec<f64>,
+    // total weight of the training rows that reached this node
+    weight: f64,
 }
 
 impl DecisionTreeNode{
     fn new() -> DecisionTreeNode{
         DecisionTreeNode{
             attribute: String::from("Null"),
+            split: None,
             children: Vec::new(),
+            class: None,
+            value: None,
+            class_dist: Vec::new(),
+            weight: 0.0,
+        }
+    }
+}
+
+// what make_tree may split on, how splits are scored, when to stop splitting,
+// and the names of the price classes it predicts
+#[derive(Clone)]
+struct TreeConfig {
+    attributes: Vec<Rc<dyn Attribute>>,
+    features: Vec<NumericFeature>,
+    criterion: Criterion,
+    max_depth: Option<usize>,
+    // nodes with fewer rows become leaves
+    min_samples_split: usize,
+    // every non-empty child of a split needs at least this many rows; empty
+    // branches of a categorical split still fall back to the parent's majority
+    min_samples_leaf: usize,
+    // entropy (or Gini) decrease a split needs to be made
+    min_impurity_decrease: f64,
+    // applied to the grown classification tree
+    pruning: Option<Pruning>,
+    // random forests look at only this many of the attributes and numeric
+    // features at every split, drawn with `rng`
+    max_features: Option<usize>,
+    rng: RefCell<Rng>,
+    labels: Vec<String>,
+}
+
+// the settings of a run that can be tuned; fit() turns them into a TreeConfig
+// once the training listings are known
+#[derive(Debug,Clone)]
+struct Settings {
+    bins: String,
+    // how many of the most common amenities the tree may split on
+    amenities: usize,
+    criterion: Criterion,
+    max_depth: Option<usize>,
+    min_samples_split: usize,
+    min_samples_leaf: usize,
+    min_impurity_decrease: f64,
+    pruning: Option<Pruning>,
+    // how train_vec is rebalanced between the price ranges before growing
+    class_weights: Option<ClassWeights>,
+    resample: Option<Resample>,
+    // seeds the resampling
+    seed: u64,
+}
+
+impl Settings{
+    // the command line options that reproduce these settings
+    fn options(&self) -> String {
+        let mut options = format!("--bins={} --amenities={} --criterion={}", self.bins, self.amenities, self.criterion.name());
+        if let Some(depth) = self.max_depth { options += &format!(" --max-depth={}", depth); }
+        options += &format!(" --min-samples-split={} --min-samples-leaf={} --min-impurity-decrease={}",
+            self.min_samples_split, self.min_samples_leaf, self.min_impurity_decrease);
+        if let Some(pruning) = &self.pruning { options += &format!(" --prune={}", pruning.name()); }
+        if let Some(weights) = &self.class_weights { options += &format!(" --class-weight={}", weights.name()); }
+        if let Some(method) = self.resample { options += &format!(" --resample={} --seed={}", method.name(), self.seed); }
+        return options;
+    }
+
+    fn from_args(args: &Vec<String>) -> Settings{
+        let criterion = match Criterion::from_name(option(args, "criterion").unwrap_or("ratio")) {
+            Some(criterion) => criterion,
+            None => { eprintln!("unknown --criterion, expected gain, ratio, gini or chi2"); process::exit(1); }
+        };
+        let pruning = match option(args, "prune").map(Pruning::from_spec) {
+            None => None,
+            Some(Ok(pruning)) => Some(pruning),
+            Some(Err(e)) => { eprintln!("--prune: {}", e); process::exit(1); }
+        };
+        let class_weights = match option(args, "class-weight").map(ClassWeights::from_spec) {
+            None => None,
+            Some(Ok(weights)) => Some(weights),
+            Some(Err(e)) => { eprintln!("--class-weight: {}", e); process::exit(1); }
+        };
+        let resample = match option(args, "resample") {
+            None => None,
+            Some(name) => match Resample::from_name(name) {
+                Some(method) => Some(method),
+                None => { eprintln!("unknown --resample={}, expected over or under", name); process::exit(1); }
+            }
+        };
+        Settings{
+            bins: String::from(option(args, "bins").unwrap_or("fixed")),
+            amenities: number_option(args, "amenities", 10),
+            criterion,
+            max_depth: option(args, "max-depth").map(|_| number_option(args, "max-depth", 0)),
+            min_samples_split: number_option(args, "min-samples-split", 2),
+            min_samples_leaf: number_option(args, "min-samples-leaf", 1).max(1),
+            min_impurity_decrease: number_option(args, "min-impurity-decrease", 0.0),
+            pruning,
+            class_weights,
+            resample,
+            seed: number_option(args, "seed", 42),
         }
     }
 }
 
+#[derive(Debug)]
+struct Prediction {
+    price: usize,
+    // share of the training weight per class at the leaf, or the blend of those
+    // shares over every leaf a listing with missing values reached; all zero
+    // at a leaf no training rows reached
+    class_dist: Vec<f64>,
+}
+
 fn main() {
-    let mut train_vec:Vec<RoomInfo> = Vec::new();
-    let mut verify_vec:Vec<RoomInfo> = Vec::new();
-    
-    let mut line_num = 0;
-    if let Ok(lines) = read_lines("test.csv") {
-        for line in lines {
-            line_num += 1;
-            if line_num == 1 { continue; }
-            if let Ok(line_str) = line {
-                if line_num % 4 == 0 {
-                    verify_vec.push(pre_treatment(line_str));
-                } else {
-                    train_vec.push(pre_treatment(line_str));
-                }
+    // usage: main [tree|eval|predict <listings.csv>|regress] [--on-error=skip|impute|fail] [--amenities=N]
+    //             [--bins=fixed|quantile:N|log:N|<edge>,<edge>,...] [--loss=squared|absolute]
+    //             [--criterion=gain|ratio|gini|chi2] [--max-depth=N] [--min-samples-split=N]
+    //             [--min-samples-leaf=N] [--min-impurity-decrease=X]
+    //             [--prune=reduced-error|pessimistic[:CF]|cost-complexity[:ALPHA]]
+    //             [--split=shuffle|stratified|group] [--verify-ratio=X] [--seed=N]
+    //             [--class-weight=balanced|<range>:<weight>,...] [--resample=over|under] [--weight-column=NAME]
+    //        main cv [--folds=K] [--repeats=N] plus the tree options above
+    //        main search [--search=grid|random:N] [--search-bins=all|<bins>;<bins>;...] [--top=N]
+    //                    plus the cv and tree options
+    //        main forest [--trees=N] [--max-features=N] [--voting=majority|probability] plus the tree options
+    //        main boost [--target=price|class] [--rounds=N] [--learning-rate=X] [--subsample=X]
+    //                   [--patience=N] plus the tree options, --max-depth defaulting to 3
+    //        main adaboost [--rounds=N] plus the tree options, --max-depth defaulting to 1
+    let args: Vec<String> = env::args().collect();
+    let positional: Vec<&str> = args.iter().skip(1).map(|a| a.as_str()).filter(|a| !a.starts_with("--")).collect();
+    let mode = if !positional.is_empty() { positional[0] } else { "eval" };
+    let policy = match option(&args, "on-error") {
+        None | Some("skip") => ErrorPolicy::Skip,
+        Some("impute") => ErrorPolicy::Impute,
+        Some("fail") => ErrorPolicy::Fail,
+        Some(other) => { eprintln!("unknown --on-error={}, expected skip, impute or fail", other); process::exit(1); }
+    };
+    let settings = Settings::from_args(&args);
+
+    let method = match Split::from_name(option(&args, "split").unwrap_or("shuffle")) {
+        Some(method) => method,
+        None => { eprintln!("unknown --split, expected shuffle, stratified or group"); process::exit(1); }
+    };
+    // share of the listings held out in verify_vec
+    let ratio = number_option(&args, "verify-ratio", 0.25);
+    if !(ratio > 0.0 && ratio < 1.0) { eprintln!("--verify-ratio must be between 0 and 1"); process::exit(1); }
+    let seed = number_option(&args, "seed", 42);
+
+    let listings = match read_listings("test.csv", true, &policy, option(&args, "weight-column")) {
+        Ok(listings) => listings,
+        Err(e) => { eprintln!("test.csv: {}", e); process::exit(1); }
+    };
+    let listings:Vec<RoomInfo> = listings.into_iter().map(|(_,roominfo)| roominfo).collect();
+
+    // cross-validation and the search over settings make their own folds out
+    // of every listing
+    let plan = CvPlan {
+        folds: number_option(&args, "folds", 5),
+        repeats: number_option(&args, "repeats", 1),
+        seed,
+        ratio,
+    };
+    if mode == "cv" {
+        match cross_validate(&listings, &settings, &plan) {
+            Ok(result) => result.print(),
+            Err(e) => { eprintln!("{}", e); process::exit(1); }
+        }
+        return;
+    }
+    if mode == "search" {
+        let search = match Search::from_spec(option(&args, "search").unwrap_or("grid")) {
+            Ok(search) => search,
+            Err(e) => { eprintln!("--search: {}", e); process::exit(1); }
+        };
+        // binnings are only searched when asked for; otherwise --bins holds
+        let binnings:Vec<String> = match option(&args, "search-bins") {
+            None => vec![settings.bins.clone()],
+            Some("all") => BINS.iter().map(|b| String::from(*b)).collect(),
+            Some(list) => list.split(";").map(String::from).collect(),
+        };
+        match run_search(&listings, &settings, &search, &binnings, &plan) {
+            Ok(results) => print_leaderboard(&results, &binnings, number_option(&args, "top", 10)),
+            Err(e) => { eprintln!("{}", e); process::exit(1); }
+        }
+        return;
+    }
+
+    let strata:Vec<usize> = match method {
+        Split::Stratified => match price_strata(&listings, &settings.bins) {
+            Ok(strata) => strata,
+            Err(e) => { eprintln!("--bins: {}", e); process::exit(1); }
+        },
+        _ => Vec::new()
+    };
+    let (mut train_vec,mut verify_vec) = match split(listings, &method, ratio, seed, &strata) {
+        Ok(split) => split,
+        Err(e) => { eprintln!("--split: {}", e); process::exit(1); }
+    };
+    // pruning that needs held-out rows and boosting's early stopping take them
+    // from the training rows, as cross-validation does, so verify_vec is scored
+    // without having been seen
+    let mut tune_vec:Vec<RoomInfo> = Vec::new();
+    let early_stopping = mode == "boost" && number_option(&args, "patience", 10) > 0;
+    if settings.pruning.is_some_and(|p| p.needs_verify()) || early_stopping {
+        (train_vec,tune_vec) = match split(train_vec, &Split::Shuffled, ratio, seed, &Vec::new()) {
+            Ok(split) => split,
+            Err(e) => { eprintln!("{}", e); process::exit(1); }
+        };
+    }
+
+    let (config,ranges) = match fit(&settings, &mut train_vec, &mut tune_vec) {
+        Ok(fitted) => fitted,
+        Err(e) => { eprintln!("{}", e); process::exit(1); }
+    };
+    ranges.assign(&mut verify_vec);
+
+    match mode {
+        "tree" => {
+            let tree_vec = prune_tree(build_tree(train_vec, &config), &config, &tune_vec);
+            print_summary(&tree_vec, &config, &format!("{:?}", config.criterion));
+            for i in 0..tree_vec.len() {
+                println!("{} - {:?}",i, tree_vec[i]);
+            }
+        },
+        "eval" => {
+            let tree_vec = build_tree(train_vec, &config);
+            if let Some(Pruning::CostComplexity(_)) = config.pruning {
+                print_path(&cost_complexity_path(&tree_vec, &config.labels), &tune_vec, &config.labels);
+            }
+            let tree_vec = prune_tree(tree_vec, &config, &tune_vec);
+            print_summary(&tree_vec, &config, &format!("{:?}", config.criterion));
+            evaluate(&tree_vec, tree_vec.len()-1, &verify_vec, &ranges.labels).print();
+        },
+        "predict" => {
+            let tree_vec = prune_tree(build_tree(train_vec, &config), &config, &tune_vec);
+            let path = if positional.len() > 1 { positional[1] } else { "verify.csv" };
+            predict_file(&tree_vec, tree_vec.len()-1, path, &policy, &ranges);
+        },
+        "regress" => {
+            let loss = match option(&args, "loss") {
+                None | Some("squared") => Loss::Squared,
+                Some("absolute") => Loss::Absolute,
+                Some(other) => { eprintln!("unknown --loss={}, expected squared or absolute", other); process::exit(1); }
+            };
+            if config.pruning.is_some() { eprintln!("--prune only applies to classification trees"); process::exit(1); }
+            let tree_vec = build_regression_tree(train_vec, &config, &loss);
+            print_summary(&tree_vec, &config, &format!("{:?} loss", loss));
+            evaluate_regression(&tree_vec, tree_vec.len()-1, &verify_vec).print();
+        },
+        "forest" => {
+            let voting = match option(&args, "voting") {
+                None | Some("probability") => Voting::Probability,
+                Some("majority") => Voting::Majority,
+                Some(other) => { eprintln!("unknown --voting={}, expected majority or probability", other); process::exit(1); }
+            };
+            // the usual square root of the number of candidates
+            let candidates = config.attributes.len() + config.features.len();
+            let forest_settings = ForestSettings {
+                trees: number_option(&args, "trees", 100).max(1),
+                max_features: number_option(&args, "max-features", ((candidates as f64).sqrt().round() as usize).max(1)),
+                voting,
+                seed,
+            };
+            if forest_settings.max_features < 1 || forest_settings.max_features > candidates {
+                eprintln!("--max-features must be between 1 and the {} candidate attributes", candidates); process::exit(1);
             }
-        }   
+            let forest = grow_forest(&train_vec, &config, &forest_settings);
+            println!("Forest: {} trees, max_features={}, voting={:?}, seed={}",
+                forest_settings.trees, forest_settings.max_features, forest_settings.voting, seed);
+            let (accuracy,count) = forest.out_of_bag_accuracy(&train_vec);
+            println!("Out-of-bag accuracy: {:.4} (over {} listings)", accuracy, count);
+            println!();
+            score(&verify_vec, &config.labels, |roominfo| forest.predict(roominfo).map(|p| p.price)).print();
+        },
+        "boost" => {
+            let boost_settings = BoostSettings {
+                rounds: number_option(&args, "rounds", 100),
+                learning_rate: number_option(&args, "learning-rate", 0.1),
+                subsample: number_option(&args, "subsample", 1.0),
+                patience: number_option(&args, "patience", 10),
+                seed,
+            };
+            if !(boost_settings.subsample > 0.0 && boost_settings.subsample <= 1.0) {
+                eprintln!("--subsample must be above 0 and at most 1"); process::exit(1);
+            }
+            // boosting wants shallow trees
+            let config = TreeConfig { max_depth: config.max_depth.or(Some(3)), ..config };
+            match option(&args, "target") {
+                None | Some("price") => {
+                    let booster = boost_price(&train_vec, &tune_vec, &config, &boost_settings);
+                    booster.print_summary(&boost_settings);
+                    score_regression(&verify_vec, |roominfo| booster.predict(roominfo)).print();
+                },
+                Some("class") => {
+                    let booster = boost_classes(&train_vec, &tune_vec, &config, &boost_settings);
+                    booster.print_summary(&boost_settings);
+                    score(&verify_vec, &config.labels, |roominfo| booster.predict(roominfo)).print();
+                },
+                Some(other) => { eprintln!("unknown --target={}, expected price or class", other); process::exit(1); }
+            }
+        },
+        "adaboost" => {
+            // stumps unless asked otherwise
+            let config = TreeConfig { max_depth: config.max_depth.or(Some(1)), ..config };
+            let rounds = number_option(&args, "rounds", 50);
+            let booster = adaboost(&train_vec, &config, rounds);
+            println!("AdaBoost (SAMME): {} of {} rounds, max_depth={}", booster.rounds(), rounds, config.max_depth.unwrap_or(1));
+            if let Some(reason) = &booster.stopped { println!("Stopped early: {}", reason); }
+            println!();
+            score(&verify_vec, &config.labels, |roominfo| booster.predict(roominfo)).print();
+        },
+        _ => println!("unknown mode '{}', expected tree, eval, predict, regress, cv, search, forest, boost or adaboost", mode)
     }
+}
 
-    let mut root:DecisionTreeNode = DecisionTreeNode::new();
+// fit the price ranges and the amenity attributes of `settings` on train_vec,
+// fill in the price classes of both sets, and return the config to grow with
+fn fit(settings: &Settings, train_vec: &mut Vec<RoomInfo>, verify_vec: &mut Vec<RoomInfo>) -> Result<(TreeConfig,PriceRanges),String> {
+    let ranges = PriceRanges::from_spec(&settings.bins, train_vec).map_err(|e| format!("--bins: {}", e))?;
+    ranges.assign(train_vec);
+    ranges.assign(verify_vec);
+    // only the training rows are rebalanced; verify_vec keeps the true mix
+    if let Some(method) = settings.resample {
+        *train_vec = resample(std::mem::take(train_vec), method, ranges.labels.len(), settings.seed);
+    }
+    if let Some(weights) = &settings.class_weights {
+        weigh_classes(train_vec, weights, &ranges.labels).map_err(|e| format!("--class-weight: {}", e))?;
+    }
+    // the stopping and pruning rules read weight sums as row counts
+    rescale_weights(train_vec);
+    let config = TreeConfig {
+        attributes: split_attributes(&top_amenities(train_vec, settings.amenities)),
+        features: numeric_features(),
+        criterion: settings.criterion,
+        max_depth: settings.max_depth,
+        min_samples_split: settings.min_samples_split,
+        min_samples_leaf: settings.min_samples_leaf,
+        min_impurity_decrease: settings.min_impurity_decrease,
+        pruning: settings.pruning,
+        max_features: None,
+        rng: RefCell::new(Rng::new(0)),
+        labels: ranges.labels.clone(),
+    };
+    return Ok((config,ranges));
+}
 
-    let mut tree_vec:Vec<DecisionTreeNode> = Vec::new();
+// scale the weights to an average of 1, so only their ratios matter
+fn rescale_weights(node_vec: &mut Vec<RoomInfo>) {
+    let total:f64 = node_vec.iter().map(|r| r.weight).sum();
+    if total == 0.0 { return; }
+    let scale = node_vec.len() as f64 / total;
+    for roominfo in node_vec { roominfo.weight *= scale; }
+}
 
+// the least weight sum that still counts as `count` rows. rescaled weights are
+// 1 only up to a rounding error, so n rows may weigh a hair under n.
+fn min_weight(count: usize) -> f64 {
+    return count as f64 * (1.0 - 1e-9);
+}
 
-    make_tree(&mut tree_vec,&mut root, train_vec);
+// value of a `--name=value` command line option
+fn option<'a>(args: &'a Vec<String>, name: &str) -> Option<&'a str> {
+    for arg in args {
+        if let Some(rest) = arg.strip_prefix("--") {
+            if let Some(value) = rest.strip_prefix(name).and_then(|r| r.strip_prefix("=")) {
+                return Some(value);
+            }
+        }
+    }
+    return None;
+}
 
-    tree_vec.push(root);
+// parse a numeric `--name=value` option, exiting with a message if it is malformed
+fn number_option<T: FromStr>(args: &Vec<String>, name: &str, default: T) -> T {
+    match option(args, name).map(|value| value.parse::<T>()) {
+        None => default,
+        Some(Ok(n)) => n,
+        Some(Err(_)) => { eprintln!("--{} expects a number", name); process::exit(1); }
+    }
+}
 
-    for i in 0..tree_vec.len() {
-        println!("{} - {:?}",i, tree_vec[i]);
+// size of the built tree and the settings it was grown with
+fn print_summary(tree_vec: &Vec<DecisionTreeNode>, config: &TreeConfig, splitting: &str) {
+    let leaves = tree_vec.iter().filter(|n| n.split.is_none()).count();
+    let max_depth = match config.max_depth { Some(d) => d.to_string(), None => String::from("none") };
+    let pruning = match &config.pruning { Some(pruning) => pruning.name(), None => String::from("none") };
+    println!("Tree: {} nodes, {} leaves, depth {}", tree_vec.len(), leaves, tree_depth(tree_vec, tree_vec.len()-1));
+    println!("Settings: {}, max_depth={}, min_samples_split={}, min_samples_leaf={}, min_impurity_decrease={}, pruning={}",
+        splitting, max_depth, config.min_samples_split, config.min_samples_leaf, config.min_impurity_decrease, pruning);
+    println!();
+}
+
+fn tree_depth(tree_vec: &Vec<DecisionTreeNode>, id: usize) -> usize {
+    tree_vec[id].children.iter().map(|c| 1 + tree_depth(tree_vec, *c)).max().unwrap_or(0)
+}
+
+// print the predicted price range for every listing in `path`; the price column may be empty.
+fn predict_file(tree_vec: &Vec<DecisionTreeNode>, root_id: usize, path: &str, policy: &ErrorPolicy, ranges: &PriceRanges) {
+    let listings = match read_listings(path, false, policy, None) {
+        Ok(listings) => listings,
+        Err(e) => { eprintln!("{}: {}", path, e); process::exit(1); }
+    };
+    for (line,roominfo) in listings {
+        match search_tree(tree_vec, root_id, &roominfo) {
+            Some(prediction) => println!("{} - {} {:?}", line, ranges.labels[prediction.price], prediction.class_dist),
+            None => println!("{} - no prediction", line)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ParseError {
+    line: usize,
+    column: String,
+    value: String,
+    reason: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: column {} has bad value {:?}: {}", self.line, self.column, self.value, self.reason)
+    }
+}
+
+// what to do with a row that has an unparsable cell
+enum ErrorPolicy {
+    Skip,   // drop the row
+    Impute, // keep the row, treating the bad cell as empty
+    Fail,   // stop at the first bad cell
+}
+
+// bad cells grouped by (column, reason)
+struct ParseSummary {
+    rows: usize,
+    rejected: usize,
+    imputed: usize,
+    reasons: BTreeMap<(String,String),usize>,
+}
+
+impl ParseSummary{
+    fn new() -> ParseSummary{
+        ParseSummary{ rows: 0, rejected: 0, imputed: 0, reasons: BTreeMap::new() }
     }
 
-    // let root_id = tree_vec.len()-1;
-    // for roominfo in verify_vec {
-    //     println!("{}", search_tree(&tree_vec, root_id, roominfo));
-    // }
-    
+    fn print(&self, path: &str) {
+        if self.reasons.is_empty() { return; }
+        eprintln!("{}: {} of {} rows rejected, {} cells imputed", path, self.rejected, self.rows, self.imputed);
+        for ((column,reason),count) in &self.reasons {
+            eprintln!("    {} x {}: {}", count, column, reason);
+        }
+    }
+}
+
+// read every listing in `path` together with the line it starts on, applying
+// `policy` to rows with bad cells and printing a summary of what was dropped.
+// `weight_column` names the column holding the sample weights, if not "weight".
+fn read_listings(path: &str, require_price: bool, policy: &ErrorPolicy, weight_column: Option<&str>) -> Result<Vec<(usize,RoomInfo)>,String> {
+    let mut reader = CsvReader::open(path).map_err(|e| e.to_string())?;
+    let header = match reader.read_record().map_err(|e| e.to_string())? {
+        Some(header) => header,
+        None => return Err(String::from("file is empty"))
+    };
+    let schema = Schema::from_header(&header, require_price, weight_column)?;
+
+    let mut listings:Vec<(usize,RoomInfo)> = Vec::new();
+    let mut summary:ParseSummary = ParseSummary::new();
+    while let Some(record) = reader.read_record().map_err(|e| e.to_string())? {
+        let line = reader.line();
+        let (roominfo,errors) = pre_treatment(&schema, &record, line);
+        summary.rows += 1;
+        for error in &errors {
+            *summary.reasons.entry((error.column.clone(),error.reason.clone())).or_insert(0) += 1;
+        }
+        // a listing to train on is no use without a price, imputed or not
+        let unpriced = require_price && roominfo.price_cents.is_none();
+        if unpriced && errors.iter().all(|e| e.column != "price") {
+            *summary.reasons.entry((String::from("price"),String::from("no price"))).or_insert(0) += 1;
+        }
+        if errors.is_empty() && !unpriced {
+            listings.push((line,roominfo));
+            continue;
+        }
+        match policy {
+            ErrorPolicy::Fail if !errors.is_empty() => return Err(errors[0].to_string()),
+            _ if unpriced => summary.rejected += 1,
+            ErrorPolicy::Skip => summary.rejected += 1,
+            _ => {
+                summary.imputed += errors.len();
+                listings.push((line,roominfo));
+            }
+        }
+    }
+    summary.print(path);
+    return Ok(listings);
 }
 
-fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
-where P: AsRef<Path>, {
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
+// one input column and the function that folds its raw cell into a RoomInfo.
+struct Column {
+    name: &'static str,
+    required: bool,
+    extract: fn(&mut RoomInfo, &str) -> Result<(),String>,
 }
 
-fn pre_treatment(origin_string: String) -> RoomInfo {
+const SCHEMA: [Column; 8] = [
+    Column { name: "room_type", required: true, extract: extract_room_type },
+    Column { name: "bedrooms", required: true, extract: extract_bedrooms },
+    Column { name: "number_of_reviews", required: true, extract: extract_reviews },
+    Column { name: "review_scores_value", required: true, extract: extract_review_score },
+    Column { name: "price", required: true, extract: extract_price },
+    Column { name: "amenities", required: true, extract: extract_amenities },
+    Column { name: "host_id", required: false, extract: extract_host_id },
+    Column { name: "weight", required: false, extract: extract_weight },
+];
+
+// position of every SCHEMA column in the file's header row
+struct Schema {
+    indices: Vec<Option<usize>>,
+}
+
+impl Schema{
+    // `require_price` is false when reading listings to predict, whose price is unknown.
+    // a weight column named by `weight_column` is required, the default one is not.
+    fn from_header(header: &Vec<String>, require_price: bool, weight_column: Option<&str>) -> Result<Schema,String> {
+        let mut indices:Vec<Option<usize>> = Vec::new();
+        let mut missing:Vec<&str> = Vec::new();
+        for column in SCHEMA.iter() {
+            let name = if column.name == "weight" { weight_column.unwrap_or(column.name) } else { column.name };
+            let required = column.required || (column.name == "weight" && weight_column.is_some());
+            let index = header.iter().position(|h| h.trim() == name);
+            if index.is_none() && required && (column.name != "price" || require_price) {
+                missing.push(name);
+            }
+            indices.push(index);
+        }
+        if !missing.is_empty() {
+            return Err(format!("missing required column(s): {}", missing.join(", ")));
+        }
+        return Ok(Schema { indices });
+    }
+}
+
+// bad cells are reported in the returned errors and filled in as if they were empty.
+fn pre_treatment(schema: &Schema, record: &Vec<String>, line: usize) -> (RoomInfo,Vec<ParseError>) {
     let mut roominfo:RoomInfo = RoomInfo::new();
-    let mut i: u32 = 1;
-    let mut amenities_num:u32 = 0;
-    for _str in origin_string.split(","){
-        match i {
-           1 => {
-                roominfo.room_type = match _str {
-                    "Private room" => RoomType::PrivateRoom,
-                    "Entire home/apt" => RoomType::EntireHomeApt,
-                    "Hotel room" => RoomType::HotelRoom,
-                    _ => RoomType::PrivateRoom
-                }
-           },
-           2 => roominfo.bedrooms = match _str {
-                "" | "1" => BedRooms::One,
-                "2" => BedRooms::Two,
-                "3" | "4" | "5" => BedRooms::Three_Five,
-                _ => BedRooms::Over_Six
-            },
-           3 => roominfo.popularity = {
-                let review_num = if _str == "" { 1 } else { _str.parse::<u16>().unwrap() };
-                if review_num < 50 {
-                    Popularity::Level1
-                } else if review_num > 200 {
-                    Popularity::Level5
-                } else { Popularity::Level3 }
-           },
-           4 => {
-                let review_score = if _str == "" { 0.0 } else { _str.parse::<f32>().unwrap() };
-                if review_score < 4.0 {
-                    roominfo.popularity = match roominfo.popularity {
-                        Popularity::Level1 => Popularity::Level1,
-                        Popularity::Level2 => Popularity::Level2,
-                        Popularity::Level3 => Popularity::Level2,
-                        Popularity::Level4 => Popularity::Level4,
-                        Popularity::Level5 => Popularity::Level4,
-                    }
-                }
-            },
-            5 => roominfo.price = {
-                let (_,price_str) = _str.split_at(1);
-                let _price= price_str.split(".").next().unwrap();
-                let price:u32 = _price.parse::<u32>().unwrap();
-                if price < 100 {
-                    PriceRange::Under100
-                } else if price < 200 {
-                    PriceRange::_100_200
-                } else if price < 300 {
-                    PriceRange::_200_300
-                } else if price < 400 {
-                    PriceRange::_300_400
-                } else if price < 500 {
-                    PriceRange::_400_500
-                } else { PriceRange::Above500 }
-            },
-            _ =>  amenities_num += 1
-        }
-        i += 1;
-    }
-    roominfo.amenities_level = {
-        if amenities_num < 10 {
-            AmenitiesLevel::Few
-        } else if amenities_num < 20 {
-            AmenitiesLevel::Common
-        } else if amenities_num < 30 {
-            AmenitiesLevel::Abundant
-        } else { AmenitiesLevel::Luxurious }
+    let mut errors:Vec<ParseError> = Vec::new();
+    for (column,index) in SCHEMA.iter().zip(schema.indices.iter()) {
+        // a missing column or a short record reads as an empty cell
+        let _str = match index {
+            Some(i) if *i < record.len() => record[*i].as_str(),
+            _ => ""
+        };
+        if let Err(reason) = (column.extract)(&mut roominfo, _str) {
+            errors.push(ParseError {
+                line,
+                column: String::from(column.name),
+                value: String::from(_str),
+                reason,
+            });
+            let _ = (column.extract)(&mut roominfo, "");
+        }
+    }
+    return (roominfo,errors);
+}
+
+fn extract_room_type(roominfo: &mut RoomInfo, _str: &str) -> Result<(),String> {
+    roominfo.room_type = match _str.trim() {
+        "" => None,
+        "Private room" => Some(RoomType::PrivateRoom),
+        "Entire home/apt" => Some(RoomType::EntireHomeApt),
+        "Hotel room" => Some(RoomType::HotelRoom),
+        "Shared room" => Some(RoomType::SharedRoom),
+        _ => { roominfo.room_type = None; return Err(String::from("unknown room type")); }
     };
-    return roominfo;
+    return Ok(());
 }
 
+fn extract_bedrooms(roominfo: &mut RoomInfo, _str: &str) -> Result<(),String> {
+    roominfo.bedrooms = if _str == "" { None } else { Some(_str.parse::<u32>().map_err(|e| e.to_string())? as f64) };
+    return Ok(());
+}
+
+fn extract_reviews(roominfo: &mut RoomInfo, _str: &str) -> Result<(),String> {
+    roominfo.number_of_reviews = if _str == "" { None } else { Some(_str.parse::<u32>().map_err(|e| e.to_string())? as f64) };
+    return Ok(());
+}
+
+fn extract_review_score(roominfo: &mut RoomInfo, _str: &str) -> Result<(),String> {
+    roominfo.review_score = None;
+    if _str == "" { return Ok(()); }
+    let review_score = _str.parse::<f64>().map_err(|e| e.to_string())?;
+    if !review_score.is_finite() { return Err(String::from("review score is not a finite number")); }
+    roominfo.review_score = Some(review_score);
+    return Ok(());
+}
+
+fn extract_price(roominfo: &mut RoomInfo, _str: &str) -> Result<(),String> {
+    roominfo.price_cents = if _str.trim() == "" { None } else { Some(parse_price_cents(_str)?) };
+    return Ok(());
+}
+
+// parse a price such as "$1,250.00", "€1.250,5", "£80" or "1 200 USD" into cents.
+// the last separator is the decimal point when both ',' and '.' appear; a lone
+// separator is a thousands separator when it is repeated or followed by exactly
+// three digits ("1,200", "1.234"), otherwise it is the decimal point ("12,5",
+// ".50"). thousands separators must leave groups of three digits.
+fn parse_price_cents(_str: &str) -> Result<u64,String> {
+    // strip currency symbols and codes on either side of the number, keeping
+    // separators and signs so they can be checked
+    let number = _str.trim_matches(|c: char| !(c.is_ascii_digit() || c == ',' || c == '.' || c == '-'));
+    if _str.trim().starts_with('-') || _str.trim().ends_with('-') || number.starts_with('-') || number.ends_with('-') {
+        return Err(String::from("negative price"));
+    }
+    if !number.chars().any(|c| c.is_ascii_digit()) { return Err(String::from("no digits in price")); }
+    if !number.ends_with(|c: char| c.is_ascii_digit()) { return Err(String::from("price ends in a separator")); }
+
+    let mut digits = String::new();
+    let mut separators:Vec<(usize,char)> = Vec::new();
+    for c in number.chars() {
+        match c {
+            '0'..='9' => digits.push(c),
+            // spaces, narrow no-break spaces and apostrophes only group thousands
+            ',' | '.' | ' ' | '\u{a0}' | '\u{202f}' | '\'' => separators.push((digits.len(),c)),
+            _ => return Err(format!("unexpected character {:?} in price", c))
+        }
+    }
+
+    let points:Vec<(usize,char)> = separators.iter().copied().filter(|&(_,c)| c == ',' || c == '.').collect();
+    let decimal:Option<usize> = match points.last() {
+        None => None,
+        Some(&(pos,c)) => {
+            let mixed = points.iter().any(|&(_,other)| other != c);
+            let repeated = points.iter().filter(|&&(_,other)| other == c).count() > 1;
+            let fraction_len = digits.len() - pos;
+            if mixed {
+                Some(pos)
+            } else if repeated || fraction_len == 3 {
+                None
+            } else {
+                Some(pos)
+            }
+        }
+    };
+
+    // every other separator groups the whole part into threes
+    let whole_len = decimal.unwrap_or(digits.len());
+    let groups:Vec<usize> = separators.iter().map(|&(pos,_)| pos).filter(|pos| Some(*pos) != decimal).collect();
+    if !groups.is_empty() {
+        let mut bounds:Vec<usize> = groups.clone();
+        bounds.push(whole_len);
+        let first_ok = groups[0] >= 1 && groups[0] <= 3;
+        if !first_ok || bounds.windows(2).any(|w| w[1] != w[0] + 3) {
+            return Err(String::from("thousands separators not in groups of three digits"));
+        }
+    }
+
+    let (whole,fraction) = digits.split_at(whole_len);
+    if fraction.len() > 2 { return Err(String::from("more than two decimal places in price")); }
+    let whole:u64 = if whole.is_empty() { 0 } else { whole.parse::<u64>().map_err(|e| e.to_string())? };
+    let fraction:u64 = match fraction.len() {
+        0 => 0,
+        1 => fraction.parse::<u64>().unwrap() * 10,
+        _ => fraction.parse::<u64>().unwrap()
+    };
+    return whole.checked_mul(100).and_then(|c| c.checked_add(fraction)).ok_or(String::from("price too large"));
+}
+
+// the amenities cell is a JSON array such as ["Wifi", "Kitchen"]
+fn extract_amenities(roominfo: &mut RoomInfo, _str: &str) -> Result<(),String> {
+    roominfo.amenities = None;
+    if _str.trim() == "" { return Ok(()); }
+    roominfo.amenities = Some(parse_amenities(_str)?);
+    return Ok(());
+}
+
+fn extract_host_id(roominfo: &mut RoomInfo, _str: &str) -> Result<(),String> {
+    roominfo.host_id = if _str.trim() == "" { None } else { Some(String::from(_str.trim())) };
+    return Ok(());
+}
+
+// a non-negative sample weight, e.g. nights available or a discount for stale
+// listings; an empty cell counts the listing once
+fn extract_weight(roominfo: &mut RoomInfo, _str: &str) -> Result<(),String> {
+    roominfo.weight = 1.0;
+    if _str.trim() == "" { return Ok(()); }
+    let weight = _str.trim().parse::<f64>().map_err(|e| e.to_string())?;
+    if !weight.is_finite() || weight < 0.0 { return Err(String::from("weight is not a non-negative number")); }
+    roominfo.weight = weight;
+    return Ok(());
+}
+
+// parse a JSON array of strings into a set of amenity names, with surrounding
+// and repeated whitespace removed so "Wifi " and "Wifi" are the same amenity.
+fn parse_amenities(_str: &str) -> Result<BTreeSet<String>,String> {
+    let mut amenities:BTreeSet<String> = BTreeSet::new();
+    let text = _str.trim();
+    let mut chars = text.chars().peekable();
+    if chars.next() != Some('[') { return Err(String::from("amenities is not a JSON array")); }
+    let mut first = true;
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) { chars.next(); }
+        match chars.next() {
+            Some(']') if first => break,
+            Some('"') => {},
+            _ => return Err(String::from("expected a quoted amenity name"))
+        }
+        let mut name = String::new();
+        loop {
+            match chars.next() {
+                Some('"') => break,
+                Some('\\') => match chars.next() {
+                    Some('n') | Some('t') | Some('r') | Some('b') | Some('f') => name.push(' '),
+                    Some('u') => {
+                        let hex:String = chars.by_ref().take(4).collect();
+                        let mut code = u32::from_str_radix(&hex, 16).map_err(|_| format!("bad escape \\u{}", hex))?;
+                        // characters outside the BMP come as a UTF-16 pair of
+                        // \u escapes; a surrogate left without its partner is replaced
+                        if (0xd800..0xdc00).contains(&code) {
+                            let mut ahead = chars.clone();
+                            if ahead.next() == Some('\\') && ahead.next() == Some('u') {
+                                let low:String = ahead.by_ref().take(4).collect();
+                                if let Some(low) = u32::from_str_radix(&low, 16).ok().filter(|l| (0xdc00..0xe000).contains(l)) {
+                                    code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                                    chars = ahead;
+                                }
+                            }
+                        }
+                        name.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                    },
+                    Some(c @ ('"' | '\\' | '/')) => name.push(c),
+                    Some(c) => return Err(format!("bad escape \\{}", c)),
+                    None => return Err(String::from("unterminated amenity name"))
+                },
+                Some(c) => name.push(c),
+                None => return Err(String::from("unterminated amenity name"))
+            }
+        }
+        first = false;
+        let name = name.split_whitespace().collect::<Vec<&str>>().join(" ");
+        if !name.is_empty() { amenities.insert(name); }
+
+        while chars.peek().is_some_and(|c| c.is_whitespace()) { chars.next(); }
+        match chars.next() {
+            Some(',') => continue,
+            Some(']') => break,
+            _ => return Err(String::from("expected ',' or ']' in amenities"))
+        }
+    }
+    return Ok(amenities);
+}
+
+// entropy of a class distribution; the entries are weighted row counts, so
+// fractional rows and boosting weights count for what they weigh.
 fn info_entropy(_vec: &Vec<f64>) -> f64 {
     let mut sum:f64 = 0.0;
     for i in _vec {
@@ -195,6 +871,7 @@ fn info_entropy(_vec: &Vec<f64>) -> f64 {
     return entropy;
 }
 
+// (weighted entropy of the branches, split information) of dist[value][class]
 fn attribute_entropy(vec_vec: &Vec<Vec<f64>>) -> (f64,f64){
     let mut res:f64 = 0.0;
     let mut all_sum:f64 = 0.0;
@@ -213,407 +890,507 @@ fn attribute_entropy(vec_vec: &Vec<Vec<f64>>) -> (f64,f64){
     return (res,info_entropy(&sum_vec));
 }
 
-fn chose_attribute(node_vec: &Vec<RoomInfo>) -> String {
-    let mut price_dist:Vec<f64> = vec![0.0;6];
+// the split scoring highest under `config.criterion` among the categorical
+// `attributes` and the best threshold of every numeric feature, or None when the
+// node is pure or nothing scores above 0 (e.g. identical feature vectors with
+// mixed prices), in which case it becomes a majority leaf.
+fn chose_attribute(node_vec: &Vec<RoomInfo>, config: &TreeConfig) -> Option<Rc<dyn Attribute>> {
+    let classes = config.labels.len();
+    let price_dist = class_dist(node_vec, classes);
+    if info_entropy(&price_dist) == 0.0 { return None; }
 
-    let mut roomtype_dist:Vec<Vec<f64>> = vec![vec![0.0;6];3];
-    let mut bedrooms_dist:Vec<Vec<f64>> = vec![vec![0.0;6];4];
-    let mut popularity_dist:Vec<Vec<f64>> = vec![vec![0.0;6];5];
-    let mut amenities_level_dist:Vec<Vec<f64>> = vec![vec![0.0;6];4];
+    // indices into the attributes followed by the numeric features
+    let mut pool:Vec<usize> = (0..config.attributes.len() + config.features.len()).collect();
+    if let Some(max_features) = config.max_features {
+        config.rng.borrow_mut().shuffle(&mut pool);
+        pool.truncate(max_features);
+        pool.sort();
+    }
+    let mut candidates:Vec<Rc<dyn Attribute>> = Vec::new();
+    for i in pool {
+        if i < config.attributes.len() {
+            candidates.push(config.attributes[i].clone());
+        } else if let Some(threshold) = best_threshold(node_vec, &config.features[i - config.attributes.len()], config) {
+            candidates.push(Rc::new(ThresholdAttribute { feature: config.features[i - config.attributes.len()], threshold }));
+        }
+    }
 
-    for roominfo in node_vec {
-        match roominfo.price {
-            PriceRange::Under100 => {
-                price_dist[0] += 1.0;
-                match roominfo.room_type {
-                    RoomType::PrivateRoom => roomtype_dist[0][0] += 1.0,
-                    RoomType::EntireHomeApt => roomtype_dist[1][0] += 1.0,
-                    RoomType::HotelRoom => roomtype_dist[2][0] += 1.0
-                };
-                match roominfo.bedrooms {
-                    BedRooms::One => bedrooms_dist[0][0] += 1.0,
-                    BedRooms::Two => bedrooms_dist[1][0] += 1.0,
-                    BedRooms::Three_Five => bedrooms_dist[2][0] += 1.0,
-                    BedRooms::Over_Six => bedrooms_dist[3][0] += 1.0
-                };
-                match roominfo.popularity {
-                    Popularity::Level1 => popularity_dist[0][0] += 1.0,
-                    Popularity::Level2 => popularity_dist[1][0] += 1.0,
-                    Popularity::Level3 => popularity_dist[2][0] += 1.0,
-                    Popularity::Level4 => popularity_dist[3][0] += 1.0,
-                    Popularity::Level5 => popularity_dist[4][0] += 1.0
-                };
-                match roominfo.amenities_level {
-                    AmenitiesLevel::Few => amenities_level_dist[0][0] += 1.0,
-                    AmenitiesLevel::Common => amenities_level_dist[1][0] += 1.0,
-                    AmenitiesLevel::Abundant => amenities_level_dist[2][0] += 1.0,
-                    AmenitiesLevel::Luxurious => amenities_level_dist[3][0] += 1.0
-                };
-            },
-            PriceRange::_100_200 =>  {
-                price_dist[1] += 1.0;
-                match roominfo.room_type {
-                    RoomType::PrivateRoom => roomtype_dist[0][1] += 1.0,
-                    RoomType::EntireHomeApt => roomtype_dist[1][1] += 1.0,
-                    RoomType::HotelRoom => roomtype_dist[2][1] += 1.0
-                };
-                match roominfo.bedrooms {
-                    BedRooms::One => bedrooms_dist[0][1] += 1.0,
-                    BedRooms::Two => bedrooms_dist[1][1] += 1.0,
-                    BedRooms::Three_Five => bedrooms_dist[2][1] += 1.0,
-                    BedRooms::Over_Six => bedrooms_dist[3][1] += 1.0
-                };
-                match roominfo.popularity {
-                    Popularity::Level1 => popularity_dist[0][1] += 1.0,
-                    Popularity::Level2 => popularity_dist[1][1] += 1.0,
-                    Popularity::Level3 => popularity_dist[2][1] += 1.0,
-                    Popularity::Level4 => popularity_dist[3][1] += 1.0,
-                    Popularity::Level5 => popularity_dist[4][1] += 1.0
-                };
-                match roominfo.amenities_level {
-                    AmenitiesLevel::Few => amenities_level_dist[0][1] += 1.0,
-                    AmenitiesLevel::Common => amenities_level_dist[1][1] += 1.0,
-                    AmenitiesLevel::Abundant => amenities_level_dist[2][1] += 1.0,
-                    AmenitiesLevel::Luxurious => amenities_level_dist[3][1] += 1.0
-                };
-            },
-            PriceRange::_200_300 =>  {
-                price_dist[2] += 1.0;
-                match roominfo.room_type {
-                    RoomType::PrivateRoom => roomtype_dist[0][2] += 1.0,
-                    RoomType::EntireHomeApt => roomtype_dist[1][2] += 1.0,
-                    RoomType::HotelRoom => roomtype_dist[2][2] += 1.0
-                };
-                match roominfo.bedrooms {
-                    BedRooms::One => bedrooms_dist[0][2] += 1.0,
-                    BedRooms::Two => bedrooms_dist[1][2] += 1.0,
-                    BedRooms::Three_Five => bedrooms_dist[2][2] += 1.0,
-                    BedRooms::Over_Six => bedrooms_dist[3][2] += 1.0
-                };
-                match roominfo.popularity {
-                    Popularity::Level1 => popularity_dist[0][2] += 1.0,
-                    Popularity::Level2 => popularity_dist[1][2] += 1.0,
-                    Popularity::Level3 => popularity_dist[2][2] += 1.0,
-                    Popularity::Level4 => popularity_dist[3][2] += 1.0,
-                    Popularity::Level5 => popularity_dist[4][2] += 1.0
-                };
-                match roominfo.amenities_level {
-                    AmenitiesLevel::Few => amenities_level_dist[0][0] += 1.0,
-                    AmenitiesLevel::Common => amenities_level_dist[1][2] += 1.0,
-                    AmenitiesLevel::Abundant => amenities_level_dist[2][2] += 1.0,
-                    AmenitiesLevel::Luxurious => amenities_level_dist[3][2] += 1.0
-                };
-            },
-            PriceRange::_300_400 =>  {
-                price_dist[3] += 1.0;
-                match roominfo.room_type {
-                    RoomType::PrivateRoom => roomtype_dist[0][3] += 1.0,
-                    RoomType::EntireHomeApt => roomtype_dist[1][3] += 1.0,
-                    RoomType::HotelRoom => roomtype_dist[2][3] += 1.0
-                };
-                match roominfo.bedrooms {
-                    BedRooms::One => bedrooms_dist[0][3] += 1.0,
-                    BedRooms::Two => bedrooms_dist[1][3] += 1.0,
-                    BedRooms::Three_Five => bedrooms_dist[2][3] += 1.0,
-                    BedRooms::Over_Six => bedrooms_dist[3][3] += 1.0
-                };
-                match roominfo.popularity {
-                    Popularity::Level1 => popularity_dist[0][3] += 1.0,
-                    Popularity::Level2 => popularity_dist[1][3] += 1.0,
-                    Popularity::Level3 => popularity_dist[2][3] += 1.0,
-                    Popularity::Level4 => popularity_dist[3][3] += 1.0,
-                    Popularity::Level5 => popularity_dist[4][3] += 1.0
-                };
-                match roominfo.amenities_level {
-                    AmenitiesLevel::Few => amenities_level_dist[0][3] += 1.0,
-                    AmenitiesLevel::Common => amenities_level_dist[1][3] += 1.0,
-                    AmenitiesLevel::Abundant => amenities_level_dist[2][3] += 1.0,
-                    AmenitiesLevel::Luxurious => amenities_level_dist[3][3] += 1.0
-                };
-            },
-            PriceRange::_400_500 =>  {
-                price_dist[4] += 1.0;
-                match roominfo.room_type {
-                    RoomType::PrivateRoom => roomtype_dist[0][4] += 1.0,
-                    RoomType::EntireHomeApt => roomtype_dist[1][4] += 1.0,
-                    RoomType::HotelRoom => roomtype_dist[2][4] += 1.0
-                };
-                match roominfo.bedrooms {
-                    BedRooms::One => bedrooms_dist[0][4] += 1.0,
-                    BedRooms::Two => bedrooms_dist[1][4] += 1.0,
-                    BedRooms::Three_Five => bedrooms_dist[2][4] += 1.0,
-                    BedRooms::Over_Six => bedrooms_dist[3][4] += 1.0
-                };
-                match roominfo.popularity {
-                    Popularity::Level1 => popularity_dist[0][4] += 1.0,
-                    Popularity::Level2 => popularity_dist[1][4] += 1.0,
-                    Popularity::Level3 => popularity_dist[2][4] += 1.0,
-                    Popularity::Level4 => popularity_dist[3][4] += 1.0,
-                    Popularity::Level5 => popularity_dist[4][4] += 1.0
-                };
-                match roominfo.amenities_level {
-                    AmenitiesLevel::Few => amenities_level_dist[0][4] += 1.0,
-                    AmenitiesLevel::Common => amenities_level_dist[1][4] += 1.0,
-                    AmenitiesLevel::Abundant => amenities_level_dist[2][4] += 1.0,
-                    AmenitiesLevel::Luxurious => amenities_level_dist[3][4] += 1.0
-                };
-            },
-            PriceRange::Above500 =>  {
-                price_dist[5] += 1.0;
-                match roominfo.room_type {
-                    RoomType::PrivateRoom => roomtype_dist[0][5] += 1.0,
-                    RoomType::EntireHomeApt => roomtype_dist[1][5] += 1.0,
-                    RoomType::HotelRoom => roomtype_dist[2][5] += 1.0
-                };
-                match roominfo.bedrooms {
-                    BedRooms::One => bedrooms_dist[0][5] += 1.0,
-                    BedRooms::Two => bedrooms_dist[1][5] += 1.0,
-                    BedRooms::Three_Five => bedrooms_dist[2][5] += 1.0,
-                    BedRooms::Over_Six => bedrooms_dist[3][5] += 1.0
-                };
-                match roominfo.popularity {
-                    Popularity::Level1 => popularity_dist[0][5] += 1.0,
-                    Popularity::Level2 => popularity_dist[1][5] += 1.0,
-                    Popularity::Level3 => popularity_dist[2][5] += 1.0,
-                    Popularity::Level4 => popularity_dist[3][5] += 1.0,
-                    Popularity::Level5 => popularity_dist[4][5] += 1.0
-                };
-                match roominfo.amenities_level {
-                    AmenitiesLevel::Few => amenities_level_dist[0][5] += 1.0,
-                    AmenitiesLevel::Common => amenities_level_dist[1][5] += 1.0,
-                    AmenitiesLevel::Abundant => amenities_level_dist[2][5] += 1.0,
-                    AmenitiesLevel::Luxurious => amenities_level_dist[3][5] += 1.0
-                };
-            },
-        }
-    }
-    let info_D = info_entropy(&price_dist);
-    if info_D == 0.0 {
-        let mut flag:bool = false;
-        for i in 0..price_dist.len() {
-            if price_dist[i] > 0.0 {
-                flag = true;
-                match i {
-                    0 => return String::from("Under100"),
-                    1 => return String::from("_100_200"),
-                    2 => return String::from("_200_300"),
-                    3 => return String::from("_300_400"),
-                    4 => return String::from("_400_500"),
-                    5 => return String::from("Above500"),
-                    _ => {return String::from("Leaf")}
+    let mut scored:Vec<(Rc<dyn Attribute>,f64,f64)> = Vec::new();
+    for attribute in candidates {
+        // rows missing the value only count through `missing`
+        let mut dist:Vec<Vec<f64>> = vec![vec![0.0;classes];attribute.num_values()];
+        let mut missing:Vec<f64> = vec![0.0;classes];
+        for roominfo in node_vec {
+            if let Some(price) = roominfo.price {
+                match attribute.value(roominfo) {
+                    Some(value) => dist[value][price] += roominfo.weight,
+                    None => missing[price] += roominfo.weight
                 }
             }
-            if flag == false { return String::from("Null"); }
         }
+        let too_small = dist.iter().map(|row| row.iter().sum::<f64>()).any(|n| n > 0.0 && n < min_weight(config.min_samples_leaf));
+        if too_small || config.criterion.impurity_decrease(&dist, &missing) < config.min_impurity_decrease { continue; }
+        let score = config.criterion.score(&dist, &missing);
+        scored.push((attribute, known_share(&dist, &missing) * info_gain(&dist), score));
     }
+    return best_scored(scored, config.criterion);
+}
 
-    let mut IGR:Vec<f64> = Vec::new();
-    let (attr_ent,attr_H) = attribute_entropy(&roomtype_dist);
-    IGR.push((info_D - attr_ent)/attr_H);
-    let (attr_ent,attr_H) = attribute_entropy(&bedrooms_dist);
-    IGR.push((info_D - attr_ent)/attr_H);
-    let (attr_ent,attr_H) = attribute_entropy(&popularity_dist);
-    IGR.push((info_D - attr_ent)/attr_H);
-    let (attr_ent,attr_H) = attribute_entropy(&amenities_level_dist);
-    IGR.push((info_D - attr_ent)/attr_H);
+// the highest scoring of the (attribute, gain, score) candidates. C4.5 only
+// trusts the ratio of attributes whose gain is at least the average, since a
+// tiny split information can inflate a useless split.
+fn best_scored(scored: Vec<(Rc<dyn Attribute>,f64,f64)>, criterion: Criterion) -> Option<Rc<dyn Attribute>> {
+    let mut min_gain:f64 = f64::NEG_INFINITY;
+    if criterion == Criterion::GainRatio {
+        let gains:Vec<f64> = scored.iter().map(|s| s.1).filter(|g| *g > 0.0).collect();
+        if !gains.is_empty() { min_gain = gains.iter().sum::<f64>() / gains.len() as f64 - 1e-12; }
+    }
 
     let mut max:f64 = 0.0;
-    let mut max_i:usize = 0;
-    for i in 0..4 {
-        if IGR[i] > max { max = IGR[i]; max_i = i; };
-    }
-    match max_i {
-        0 => return String::from("RoomType"),
-        1 => return String::from("BedRooms"),
-        2 => return String::from("Popularity"),
-        3 => return String::from("AmenitiesLevel"),
-        _ => return String::from("Leaf")
-    }
-}
-
-fn make_tree(tree_vec: &mut Vec<DecisionTreeNode>, node: &mut DecisionTreeNode, node_vec: Vec<RoomInfo>){
-    let choice = chose_attribute(&node_vec);
-    match &choice as &str{
-        "RoomType" => {
-            node.attribute = String::from("RoomType");
-            let mut node1: DecisionTreeNode = DecisionTreeNode::new();
-            let mut node2: DecisionTreeNode = DecisionTreeNode::new();
-            let mut node3: DecisionTreeNode = DecisionTreeNode::new();
-            let mut nodevec1: Vec<RoomInfo> = Vec::new();
-            let mut nodevec2: Vec<RoomInfo> = Vec::new();
-            let mut nodevec3: Vec<RoomInfo> = Vec::new();
-
-            for roominfo in node_vec {
-                match roominfo.room_type {
-                    RoomType::PrivateRoom => nodevec1.push(roominfo),
-                    RoomType::EntireHomeApt => nodevec2.push(roominfo),
-                    RoomType::HotelRoom => nodevec3.push(roominfo),
-                }
-            }
-            make_tree(tree_vec,&mut node1,nodevec1);
-            tree_vec.push(node1); 
-            node.children.push(tree_vec.len()-1);
-            make_tree(tree_vec,&mut node2,nodevec2);
-            tree_vec.push(node2); 
-            node.children.push(tree_vec.len()-1);
-            make_tree(tree_vec,&mut node3,nodevec3);
-            tree_vec.push(node3); 
-            node.children.push(tree_vec.len()-1);
-        },
-        "BedRooms" => {
-            node.attribute = String::from("BedRooms");
-            let mut node1: DecisionTreeNode = DecisionTreeNode::new();
-            let mut node2: DecisionTreeNode = DecisionTreeNode::new();
-            let mut node3: DecisionTreeNode = DecisionTreeNode::new();
-            let mut node4: DecisionTreeNode = DecisionTreeNode::new();
-            let mut nodevec1: Vec<RoomInfo> = Vec::new();
-            let mut nodevec2: Vec<RoomInfo> = Vec::new();
-            let mut nodevec3: Vec<RoomInfo> = Vec::new();
-            let mut nodevec4: Vec<RoomInfo> = Vec::new();
-
-            for roominfo in node_vec {
-                match roominfo.bedrooms {
-                    BedRooms::One => nodevec1.push(roominfo),
-                    BedRooms::Two => nodevec2.push(roominfo),
-                    BedRooms::Three_Five => nodevec3.push(roominfo),
-                    BedRooms::Over_Six => nodevec4.push(roominfo),
-                }
-            }
-            make_tree(tree_vec,&mut node1,nodevec1);
-            tree_vec.push(node1); 
-            node.children.push(tree_vec.len()-1);
-            make_tree(tree_vec,&mut node2,nodevec2);
-            tree_vec.push(node2); 
-            node.children.push(tree_vec.len()-1);
-            make_tree(tree_vec,&mut node3,nodevec3);
-            tree_vec.push(node3); 
-            node.children.push(tree_vec.len()-1);
-            make_tree(tree_vec,&mut node4,nodevec4);
-            tree_vec.push(node4); 
-            node.children.push(tree_vec.len()-1);
-        },
-        "Popularity" => {
-            node.attribute = String::from("Popularity");
-            let mut node1: DecisionTreeNode = DecisionTreeNode::new();
-            let mut node2: DecisionTreeNode = DecisionTreeNode::new();
-            let mut node3: DecisionTreeNode = DecisionTreeNode::new();
-            let mut node4: DecisionTreeNode = DecisionTreeNode::new();
-            let mut node5: DecisionTreeNode = DecisionTreeNode::new();
-            let mut nodevec1: Vec<RoomInfo> = Vec::new();
-            let mut nodevec2: Vec<RoomInfo> = Vec::new();
-            let mut nodevec3: Vec<RoomInfo> = Vec::new();
-            let mut nodevec4: Vec<RoomInfo> = Vec::new();
-            let mut nodevec5: Vec<RoomInfo> = Vec::new();
-
-            for roominfo in node_vec {
-                match roominfo.popularity {
-                    Popularity::Level1 => nodevec1.push(roominfo),
-                    Popularity::Level2 => nodevec2.push(roominfo),
-                    Popularity::Level3 => nodevec3.push(roominfo),
-                    Popularity::Level4 => nodevec4.push(roominfo),
-                    Popularity::Level5 => nodevec5.push(roominfo),
-                }
-            }
-            make_tree(tree_vec,&mut node1,nodevec1);
-            tree_vec.push(node1); 
-            node.children.push(tree_vec.len()-1);
-            make_tree(tree_vec,&mut node2,nodevec2);
-            tree_vec.push(node2); 
-            node.children.push(tree_vec.len()-1);
-            make_tree(tree_vec,&mut node3,nodevec3);
-            tree_vec.push(node3); 
-            node.children.push(tree_vec.len()-1);
-            make_tree(tree_vec,&mut node4,nodevec4);
-            tree_vec.push(node4); 
-            node.children.push(tree_vec.len()-1);
-            make_tree(tree_vec,&mut node5,nodevec5);
-            tree_vec.push(node5);
-            node.children.push(tree_vec.len()-1);
-        },
-        "AmenitiesLevel" => {
-            node.attribute = String::from("AmenitiesLevel");
-            let mut node1: DecisionTreeNode = DecisionTreeNode::new();
-            let mut node2: DecisionTreeNode = DecisionTreeNode::new();
-            let mut node3: DecisionTreeNode = DecisionTreeNode::new();
-            let mut node4: DecisionTreeNode = DecisionTreeNode::new();
-            let mut nodevec1: Vec<RoomInfo> = Vec::new();
-            let mut nodevec2: Vec<RoomInfo> = Vec::new();
-            let mut nodevec3: Vec<RoomInfo> = Vec::new();
-            let mut nodevec4: Vec<RoomInfo> = Vec::new();
-
-            for roominfo in node_vec {
-                match roominfo.amenities_level {
-                    AmenitiesLevel::Few => nodevec1.push(roominfo),
-                    AmenitiesLevel::Common => nodevec2.push(roominfo),
-                    AmenitiesLevel::Abundant => nodevec3.push(roominfo),
-                    AmenitiesLevel::Luxurious => nodevec4.push(roominfo),
-                }
-            }
-            make_tree(tree_vec,&mut node1,nodevec1);
-            tree_vec.push(node1); 
-            node.children.push(tree_vec.len()-1);
-            make_tree(tree_vec,&mut node2,nodevec2);
-            tree_vec.push(node2); 
-            node.children.push(tree_vec.len()-1);
-            make_tree(tree_vec,&mut node3,nodevec3);
-            tree_vec.push(node3); 
-            node.children.push(tree_vec.len()-1);
-            make_tree(tree_vec,&mut node4,nodevec4);
-            tree_vec.push(node4); 
-            node.children.push(tree_vec.len()-1);
-        },
-        "Under100" => node.attribute = String::from("Under100"),
-        "_100_200" => node.attribute = String::from("_100_200"),
-        "_200_300" => node.attribute = String::from("_200_300"),
-        "_300_400" => node.attribute = String::from("_300_400"),
-        "_400_500" => node.attribute = String::from("_400_500"),
-        "Above500" => node.attribute = String::from("Above500"),
-        _ => {}
-    }
-}
-
-fn search_tree(tree_vec: &Vec<DecisionTreeNode>,id: usize,roominfo: RoomInfo) -> bool{
-    let label = &tree_vec[id].attribute;
-    match label as &str {
-        "RoomType" => {
-            match roominfo.room_type {
-                RoomType::PrivateRoom => return search_tree(tree_vec,tree_vec[id].children[0],roominfo),
-                RoomType::EntireHomeApt => return search_tree(tree_vec,tree_vec[id].children[1],roominfo),
-                RoomType::HotelRoom => return search_tree(tree_vec,tree_vec[id].children[2],roominfo),
-            }
-        },
-        "BedRooms" => {
-            match roominfo.bedrooms {
-                BedRooms::One => return search_tree(tree_vec,tree_vec[id].children[0],roominfo),
-                BedRooms::Two => return search_tree(tree_vec,tree_vec[id].children[1],roominfo),
-                BedRooms::Three_Five => return search_tree(tree_vec,tree_vec[id].children[2],roominfo),
-                BedRooms::Over_Six => return search_tree(tree_vec,tree_vec[id].children[3],roominfo),
-            }
-        },
-        "Popularity" => {
-            match roominfo.popularity {
-                Popularity::Level1 => return search_tree(tree_vec,tree_vec[id].children[0],roominfo),
-                Popularity::Level2 => return search_tree(tree_vec,tree_vec[id].children[1],roominfo),
-                Popularity::Level3 => return search_tree(tree_vec,tree_vec[id].children[2],roominfo),
-                Popularity::Level4 => return search_tree(tree_vec,tree_vec[id].children[3],roominfo),
-                Popularity::Level5 => return search_tree(tree_vec,tree_vec[id].children[4],roominfo),
-            }
-        },
-        "AmenitiesLevel" => {
-            match roominfo.amenities_level {
-                AmenitiesLevel::Few => return search_tree(tree_vec,tree_vec[id].children[0],roominfo),
-                AmenitiesLevel::Common => return search_tree(tree_vec,tree_vec[id].children[1],roominfo),
-                AmenitiesLevel::Abundant => return search_tree(tree_vec,tree_vec[id].children[2],roominfo),
-                AmenitiesLevel::Luxurious => return search_tree(tree_vec,tree_vec[id].children[3],roominfo),
+    let mut max_attribute:Option<Rc<dyn Attribute>> = None;
+    for (attribute,gain,score) in scored {
+        if gain < min_gain || score.is_nan() { continue; }
+        if score > max { max = score; max_attribute = Some(attribute); }
+    }
+    return max_attribute;
+}
+
+// C4.5 threshold search: sort the rows with a known value on the feature and
+// try `<= v` for every observed value v followed by a larger one, keeping the
+// cut with the highest threshold score. the winner then competes with the
+// other attributes in chose_attribute.
+fn best_threshold(node_vec: &Vec<RoomInfo>, feature: &NumericFeature, config: &TreeConfig) -> Option<f64> {
+    let classes = config.labels.len();
+    let mut values:Vec<(f64,usize,f64)> = Vec::new();
+    for roominfo in node_vec {
+        if let (Some(value),Some(price)) = ((feature.get)(roominfo),roominfo.price) {
+            values.push((value, price, roominfo.weight));
+        }
+    }
+    values.sort_by(|a,b| a.0.total_cmp(&b.0));
+
+    let mut dist:Vec<Vec<f64>> = vec![vec![0.0;classes];2];
+    for (_,class,weight) in &values { dist[1][*class] += weight; }
+    let min_leaf = min_weight(config.min_samples_leaf);
+    let mut max:f64 = 0.0;
+    let mut threshold:Option<f64> = None;
+    for i in 0..values.len().saturating_sub(1) {
+        let (value,class,weight) = values[i];
+        dist[0][class] += weight;
+        dist[1][class] -= weight;
+        if values[i+1].0 == value { continue; }
+        if dist[0].iter().sum::<f64>() < min_leaf || dist[1].iter().sum::<f64>() < min_leaf { continue; }
+        let score = config.criterion.threshold_score(&dist);
+        if score > max { max = score; threshold = Some(value); }
+    }
+    return threshold;
+}
+
+// the `n` amenities offered by the most training listings, most common first
+fn top_amenities(node_vec: &Vec<RoomInfo>, n: usize) -> Vec<String> {
+    let mut counts:BTreeMap<&String,usize> = BTreeMap::new();
+    for roominfo in node_vec {
+        for amenity in roominfo.amenities.iter().flatten() {
+            *counts.entry(amenity).or_insert(0) += 1;
+        }
+    }
+    let mut counts:Vec<(&String,usize)> = counts.into_iter().collect();
+    // stable sort keeps ties in alphabetical order
+    counts.sort_by_key(|c| std::cmp::Reverse(c.1));
+    return counts.into_iter().take(n).map(|(name,_)| name.clone()).collect();
+}
+
+// index of the most frequent class, ties going to the cheaper range.
+// None when the distribution is empty.
+fn majority_class(dist: &Vec<f64>) -> Option<usize> {
+    let mut max:f64 = 0.0;
+    let mut max_i:Option<usize> = None;
+    for i in 0..dist.len() {
+        if dist[i] > max { max = dist[i]; max_i = Some(i); }
+    }
+    return max_i;
+}
+
+// build a classification tree on `node_vec`; the root is the last node.
+fn build_tree(node_vec: Vec<RoomInfo>, config: &TreeConfig) -> Vec<DecisionTreeNode> {
+    let mut tree_vec:Vec<DecisionTreeNode> = Vec::new();
+    let mut root:DecisionTreeNode = DecisionTreeNode::new();
+    let fallback = majority_class(&class_dist(&node_vec, config.labels.len())).unwrap_or(0);
+    make_tree(&mut tree_vec,&mut root, node_vec, fallback, 0, config);
+    tree_vec.push(root);
+    return tree_vec;
+}
+
+// `fallback` is the parent's majority class, used as the label of an empty partition.
+// `depth` is 0 at the root.
+fn make_tree(tree_vec: &mut Vec<DecisionTreeNode>, node: &mut DecisionTreeNode, node_vec: Vec<RoomInfo>, fallback: usize, depth: usize, config: &TreeConfig){
+    node.class_dist = class_dist(&node_vec, config.labels.len());
+    node.weight = node_vec.iter().fold(0.0, |w,r| w + r.weight);
+    let majority = majority_class(&node.class_dist).unwrap_or(fallback);
+    let stop = node_vec.is_empty() || node.weight < min_weight(config.min_samples_split) || config.max_depth.is_some_and(|d| depth >= d);
+    let choice = if stop { None } else { chose_attribute(&node_vec, config) };
+    let attribute = match choice {
+        Some(attribute) => attribute,
+        None => {
+            node.attribute = config.labels[majority].clone();
+            node.class = Some(majority);
+            return;
+        }
+    };
+
+    // children are pushed in branch order, so children[v] holds the rows with value v
+    node.attribute = attribute.name();
+    for nodevec in partition(attribute.as_ref(), node_vec) {
+        let mut child: DecisionTreeNode = DecisionTreeNode::new();
+        make_tree(tree_vec,&mut child,nodevec,majority,depth+1,config);
+        tree_vec.push(child);
+        node.children.push(tree_vec.len()-1);
+    }
+    node.split = Some(attribute);
+}
+
+// apply the configured post-pruning, if any; reduced-error and the automatic
+// cost-complexity alpha are judged on `verify_vec`.
+fn prune_tree(tree_vec: Vec<DecisionTreeNode>, config: &TreeConfig, verify_vec: &Vec<RoomInfo>) -> Vec<DecisionTreeNode> {
+    match &config.pruning {
+        Some(pruning) => prune(tree_vec, pruning, verify_vec, &config.labels),
+        None => tree_vec
+    }
+}
+
+// split the rows on `attribute`, children[v] getting the rows with value v. as
+// in C4.5 a row missing the value goes down every branch, its weight scaled by
+// the share of the known weight that branch received.
+fn partition(attribute: &dyn Attribute, node_vec: Vec<RoomInfo>) -> Vec<Vec<RoomInfo>> {
+    let mut nodevecs:Vec<Vec<RoomInfo>> = (0..attribute.num_values()).map(|_| Vec::new()).collect();
+    let mut missing:Vec<RoomInfo> = Vec::new();
+    for roominfo in node_vec {
+        match attribute.value(&roominfo) {
+            Some(value) => nodevecs[value].push(roominfo),
+            None => missing.push(roominfo)
+        }
+    }
+    let weights:Vec<f64> = nodevecs.iter().map(|v| v.iter().map(|r| r.weight).sum()).collect();
+    let known:f64 = weights.iter().sum();
+    for roominfo in missing {
+        for (nodevec,weight) in nodevecs.iter_mut().zip(&weights) {
+            if *weight == 0.0 { continue; }
+            let mut share = roominfo.clone();
+            share.weight *= weight / known;
+            nodevec.push(share);
+        }
+    }
+    return nodevecs;
+}
+
+// predict the price class of a listing; its own price is never looked at. a
+// listing missing the value a node splits on goes down every branch, and what
+// the branches predict is blended by their share of the training weight.
+// returns None only when no branch the listing reached held any training weight.
+fn search_tree(tree_vec: &Vec<DecisionTreeNode>,id: usize,roominfo: &RoomInfo) -> Option<Prediction>{
+    let node = &tree_vec[id];
+    let attribute = match &node.split {
+        Some(attribute) => attribute,
+        None => return node.class.map(|price| Prediction { price, class_dist: proportions(&node.class_dist) })
+    };
+    if let Some(value) = attribute.value(roominfo) {
+        return search_tree(tree_vec,node.children[value],roominfo);
+    }
+    let mut class_dist:Vec<f64> = vec![0.0;node.class_dist.len()];
+    for child in &node.children {
+        let share = tree_vec[*child].weight / node.weight;
+        if share == 0.0 { continue; }
+        if let Some(prediction) = search_tree(tree_vec,*child,roominfo) {
+            for (k,p) in prediction.class_dist.iter().enumerate() { class_dist[k] += share * p; }
+        }
+    }
+    let price = majority_class(&class_dist).or(majority_class(&node.class_dist))?;
+    return Some(Prediction { price, class_dist: proportions(&class_dist) });
+}
+
+// counts scaled to sum to 1, or left at zero when there are none
+fn proportions(counts: &Vec<f64>) -> Vec<f64> {
+    let total:f64 = counts.iter().sum();
+    if total == 0.0 { return counts.clone(); }
+    return counts.iter().map(|c| c / total).collect();
+}
+
+fn class_dist(node_vec: &Vec<RoomInfo>, classes: usize) -> Vec<f64> {
+    let mut dist:Vec<f64> = vec![0.0;classes];
+    for roominfo in node_vec {
+        if let Some(price) = roominfo.price { dist[price] += roominfo.weight; }
+    }
+    return dist;
+}
+
+// confusion[actual][predicted] in listing weight; listings the model makes no
+// prediction for are counted per actual class in `unpredicted` so they still
+// pull recall and accuracy down.
+struct EvalReport {
+    labels: Vec<String>,
+    confusion: Vec<Vec<f64>>,
+    unpredicted: Vec<f64>,
+    // plain counts, for the summary line
+    listings: usize,
+    unpredicted_listings: usize,
+}
+
+impl EvalReport{
+    fn new(labels: &Vec<String>) -> EvalReport{
+        EvalReport{
+            labels: labels.clone(),
+            confusion: vec![vec![0.0;labels.len()];labels.len()],
+            unpredicted: vec![0.0;labels.len()],
+            listings: 0,
+            unpredicted_listings: 0,
+        }
+    }
+
+    fn total(&self) -> f64 {
+        let mut sum:f64 = 0.0;
+        for i in 0..self.confusion.len() { sum += self.support(i); }
+        return sum;
+    }
+
+    fn support(&self, class: usize) -> f64 {
+        return self.confusion[class].iter().sum::<f64>() + self.unpredicted[class];
+    }
+
+    fn accuracy(&self) -> f64 {
+        let total = self.total();
+        if total == 0.0 { return 0.0; }
+        let mut correct:f64 = 0.0;
+        for i in 0..self.confusion.len() { correct += self.confusion[i][i]; }
+        return correct / total;
+    }
+
+    fn precision(&self, class: usize) -> f64 {
+        let mut predicted:f64 = 0.0;
+        for row in &self.confusion { predicted += row[class]; }
+        if predicted == 0.0 { return 0.0; }
+        return self.confusion[class][class] / predicted;
+    }
+
+    fn recall(&self, class: usize) -> f64 {
+        let support = self.support(class);
+        if support == 0.0 { return 0.0; }
+        return self.confusion[class][class] / support;
+    }
+
+    fn f1(&self, class: usize) -> f64 {
+        let (p,r) = (self.precision(class),self.recall(class));
+        if p + r == 0.0 { return 0.0; }
+        return 2.0 * p * r / (p + r);
+    }
+
+    // macro averages only cover classes that occur in the held-out split.
+    fn macro_avg(&self) -> (f64,f64,f64) {
+        let (mut p,mut r,mut f,mut n) = (0.0,0.0,0.0,0.0);
+        for i in 0..self.confusion.len() {
+            if self.support(i) == 0.0 { continue; }
+            p += self.precision(i);
+            r += self.recall(i);
+            f += self.f1(i);
+            n += 1.0;
+        }
+        if n == 0.0 { return (0.0,0.0,0.0); }
+        return (p/n,r/n,f/n);
+    }
+
+    fn weighted_avg(&self) -> (f64,f64,f64) {
+        let total = self.total();
+        if total == 0.0 { return (0.0,0.0,0.0); }
+        let (mut p,mut r,mut f) = (0.0,0.0,0.0);
+        for i in 0..self.confusion.len() {
+            let w = self.support(i) / total;
+            p += w * self.precision(i);
+            r += w * self.recall(i);
+            f += w * self.f1(i);
+        }
+        return (p,r,f);
+    }
+
+    fn print(&self) {
+        if self.unpredicted_listings == 0 {
+            println!("Evaluated {} listings", self.listings);
+        } else {
+            println!("Evaluated {} listings ({} without a prediction)", self.listings, self.unpredicted_listings);
+        }
+        if self.total() != self.listings as f64 { println!("Total weight: {}", self.total()); }
+        println!("Accuracy: {:.4}", self.accuracy());
+        println!();
+        println!("Confusion matrix (rows = actual, columns = predicted):");
+        let width = self.labels.iter().map(|l| l.len() + 2).max().unwrap_or(0).max(10);
+        print!("{:>width$}", "");
+        for label in &self.labels { print!("{:>width$}", label); }
+        println!();
+        for i in 0..self.confusion.len() {
+            print!("{:>width$}", self.labels[i]);
+            for j in 0..self.confusion[i].len() { print!("{:>width$}", self.confusion[i][j]); }
+            println!();
+        }
+        println!();
+        let width = width.max(12);
+        println!("{:>width$}{:>11}{:>11}{:>11}{:>9}", "", "precision", "recall", "f1", "support");
+        for i in 0..self.confusion.len() {
+            println!("{:>width$}{:>11.4}{:>11.4}{:>11.4}{:>9}",
+                self.labels[i], self.precision(i), self.recall(i), self.f1(i), self.support(i));
+        }
+        let (p,r,f) = self.macro_avg();
+        println!("{:>width$}{:>11.4}{:>11.4}{:>11.4}{:>9}", "macro avg", p, r, f, self.total());
+        let (p,r,f) = self.weighted_avg();
+        println!("{:>width$}{:>11.4}{:>11.4}{:>11.4}{:>9}", "weighted avg", p, r, f, self.total());
+    }
+}
+
+fn evaluate(tree_vec: &Vec<DecisionTreeNode>, root_id: usize, verify_vec: &Vec<RoomInfo>, labels: &Vec<String>) -> EvalReport {
+    score(verify_vec, labels, |roominfo| search_tree(tree_vec,root_id,roominfo).map(|p| p.price))
+}
+
+// tally what any model predicts for the listings against their price class
+fn score<F: Fn(&RoomInfo) -> Option<usize>>(verify_vec: &Vec<RoomInfo>, labels: &Vec<String>, predict: F) -> EvalReport {
+    let mut report:EvalReport = EvalReport::new(labels);
+    for roominfo in verify_vec {
+        let actual = match roominfo.price {
+            Some(price) => price,
+            None => continue
+        };
+        report.listings += 1;
+        match predict(roominfo) {
+            Some(price) => report.confusion[actual][price] += roominfo.weight,
+            None => {
+                report.unpredicted[actual] += roominfo.weight;
+                report.unpredicted_listings += 1;
             }
         }
-        "Under100" => if roominfo.price == PriceRange::Under100 { return true;} else { return false},
-        "_100_200" => if roominfo.price == PriceRange::_100_200 { return true;} else { return false},
-        "_200_300" => if roominfo.price == PriceRange::_200_300 { return true;} else { return false},
-        "_300_400" => if roominfo.price == PriceRange::_300_400 { return true;} else { return false},
-        "_400_500" => if roominfo.price == PriceRange::_400_500 { return true;} else { return false},
-        "Above500" => if roominfo.price == PriceRange::Above500 { return true;} else { return false},
-        "Null" => return false,
-        _ => {return false;}
+    }
+    return report;
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::attribute::AmenityAttribute;
+
+    // `n` listings whose price rises with the bedrooms and the reviews, so
+    // trees have something to split on, with the classes and the config fitted
+    pub fn fitted(n: usize) -> (Vec<RoomInfo>,TreeConfig) {
+        let mut train_vec:Vec<RoomInfo> = listings(n);
+        let settings = Settings::from_args(&Vec::new());
+        let (config,_) = fit(&settings, &mut train_vec, &mut Vec::new()).unwrap();
+        return (train_vec,config);
+    }
+
+    pub fn listings(n: usize) -> Vec<RoomInfo> {
+        (0..n).map(|i| {
+            let mut roominfo = RoomInfo::new();
+            roominfo.room_type = Some(if i % 3 == 0 { RoomType::EntireHomeApt } else { RoomType::PrivateRoom });
+            roominfo.bedrooms = Some((i % 4) as f64);
+            roominfo.number_of_reviews = Some((i * 7 % 11) as f64);
+            roominfo.price_cents = Some(40_00 + 60_00 * (i % 4) as u64 + 5_00 * (i * 7 % 11) as u64);
+            roominfo
+        }).collect()
+    }
+
+    #[test]
+    fn parses_prices() {
+        let cases:Vec<(&str,Result<u64,()>)> = vec![
+            ("$1,250.00", Ok(125000)),
+            ("€1.250,5", Ok(125050)),
+            ("£80", Ok(8000)),
+            ("1 200 USD", Ok(120000)),
+            ("1,200", Ok(120000)),
+            ("1.234", Ok(123400)),
+            ("12,5", Ok(1250)),
+            ("$0.00", Ok(0)),
+            ("$.50", Ok(50)),
+            ("1,234,567.89", Ok(123456789)),
+            ("1.234.567,89", Ok(123456789)),
+            ("1'234.50", Ok(123450)),
+            ("CHF 1\u{202f}234", Ok(123400)),
+            ("$5-", Err(())),
+            ("-$5", Err(())),
+            ("$-5", Err(())),
+            ("12.3.4", Err(())),
+            ("1,2,3", Err(())),
+            ("1,5.000", Err(())),
+            ("12 34", Err(())),
+            ("12.", Err(())),
+            ("1.2345", Err(())),
+            ("$", Err(())),
+            ("", Err(())),
+            ("12a3", Err(())),
+        ];
+        for (price,expected) in cases {
+            assert_eq!(parse_price_cents(price).map_err(|_| ()), expected, "{:?}", price);
+        }
+    }
+
+    #[test]
+    fn gain_ratio_skips_below_average_gain() {
+        let candidate = |name: &str, gain: f64, score: f64| -> (Rc<dyn Attribute>,f64,f64) {
+            (Rc::new(AmenityAttribute { amenity: String::from(name) }), gain, score)
+        };
+        // "narrow" has the best ratio but a gain below the 0.333 average
+        let scored = || vec![candidate("wide", 0.5, 0.3), candidate("narrow", 0.1, 0.9), candidate("fair", 0.4, 0.35)];
+        let name = |best: Option<Rc<dyn Attribute>>| best.map(|a| a.name());
+        assert_eq!(name(best_scored(scored(), Criterion::GainRatio)), Some(String::from("Amenity:fair")));
+        assert_eq!(name(best_scored(scored(), Criterion::InfoGain)), Some(String::from("Amenity:narrow")));
+    }
+
+    #[test]
+    fn joins_surrogate_pairs() {
+        let amenities = parse_amenities(r#"["Smile \uD83D\uDE00", "Lone \uD83D", "Caf\u00e9"]"#).unwrap();
+        let expected:Vec<&str> = vec!["Caf\u{e9}", "Lone \u{fffd}", "Smile \u{1f600}"];
+        assert_eq!(amenities.iter().map(|a| a.as_str()).collect::<Vec<&str>>(), expected);
+    }
+
+    #[test]
+    fn control_escapes_become_spaces() {
+        let amenities = parse_amenities(r#"["Wifi\bfast", "Hot\ftub", "TV \/ cable", "\"Smart\" \\ lock"]"#).unwrap();
+        let expected:Vec<&str> = vec!["\"Smart\" \\ lock", "Hot tub", "TV / cable", "Wifi fast"];
+        assert_eq!(amenities.iter().map(|a| a.as_str()).collect::<Vec<&str>>(), expected);
+        assert!(parse_amenities(r#"["Wifi\x"]"#).is_err());
+    }
+
+    #[test]
+    fn weight_column_is_looked_up_by_name() {
+        let header:Vec<String> = ["room_type", "bedrooms", "number_of_reviews", "review_scores_value", "price", "amenities", "nights"]
+            .iter().map(|h| String::from(*h)).collect();
+        let record:Vec<String> = ["Private room", "1", "2", "4.5", "$80", "[]", "3"].iter().map(|c| String::from(*c)).collect();
+        let named = Schema::from_header(&header, true, Some("nights")).unwrap();
+        assert_eq!(pre_treatment(&named, &record, 2).0.weight, 3.0);
+        let default = Schema::from_header(&header, true, None).unwrap();
+        assert_eq!(pre_treatment(&default, &record, 2).0.weight, 1.0);
+        assert!(Schema::from_header(&header, true, Some("stays")).is_err());
+    }
+
+    #[test]
+    fn rescaled_weights_still_count_as_rows() {
+        let mut rows = listings(3);
+        for roominfo in &mut rows { roominfo.weight = 0.1; }
+        rescale_weights(&mut rows);
+        let total:f64 = rows.iter().map(|r| r.weight).sum();
+        // three rows of 0.1 rescale to a hair under 3
+        assert!(total < 3.0);
+        assert!(total >= min_weight(3));
+    }
+
+    #[test]
+    fn empty_amenities_cell_is_missing() {
+        let wifi = AmenityAttribute { amenity: String::from("Wifi") };
+        let count = numeric_features().into_iter().find(|f| f.name == "amenities_count").unwrap();
+        let cases:Vec<(&str,Option<usize>,Option<f64>)> = vec![
+            ("", None, None),
+            ("  ", None, None),
+            ("[]", Some(0), Some(0.0)),
+            (r#"["Wifi", "Kitchen"]"#, Some(1), Some(2.0)),
+        ];
+        for (cell,has_wifi,amenities) in cases {
+            let mut roominfo = RoomInfo::new();
+            extract_amenities(&mut roominfo, cell).unwrap();
+            assert_eq!(wifi.value(&roominfo), has_wifi, "{:?}", cell);
+            assert_eq!((count.get)(&roominfo), amenities, "{:?}", cell);
+        }
     }
 }
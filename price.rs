@@ -0,0 +1,112 @@
+// how nightly prices are cut into the classes the tree predicts. class i holds
+// the prices in [edges[i-1], edges[i]), so n edges give n+1 classes.
+use crate::{RoomInfo, parse_price_cents};
+
+#[derive(Debug)]
+pub struct PriceRanges {
+    // in cents, strictly increasing and above zero
+    pub edges: Vec<u64>,
+    pub labels: Vec<String>,
+}
+
+impl PriceRanges {
+    pub fn from_edges(mut edges: Vec<u64>) -> PriceRanges {
+        edges.sort();
+        edges.dedup();
+        edges.retain(|e| *e > 0);
+        let mut labels:Vec<String> = Vec::new();
+        for i in 0..=edges.len() {
+            labels.push(if edges.is_empty() {
+                String::from("Any")
+            } else if i == 0 {
+                format!("Under{}", dollars(edges[0]))
+            } else if i == edges.len() {
+                format!("Above{}", dollars(edges[i-1]))
+            } else {
+                format!("_{}_{}", dollars(edges[i-1]), dollars(edges[i]))
+            });
+        }
+        return PriceRanges { edges, labels };
+    }
+
+    // the original $100-wide ranges: Under100, _100_200, ... , Above500
+    pub fn fixed() -> PriceRanges {
+        PriceRanges::from_edges(vec![10_000, 20_000, 30_000, 40_000, 50_000])
+    }
+
+    // `n` ranges holding about the same number of listings each. repeated
+    // prices can merge neighbouring edges, leaving fewer ranges.
+    pub fn quantile(prices: &Vec<u64>, n: usize) -> PriceRanges {
+        let mut sorted = prices.clone();
+        sorted.sort();
+        let mut edges:Vec<u64> = Vec::new();
+        if !sorted.is_empty() {
+            for i in 1..n {
+                edges.push(sorted[i * sorted.len() / n]);
+            }
+        }
+        PriceRanges::from_edges(edges)
+    }
+
+    // `n` ranges of equal width on a log scale between the cheapest and the
+    // dearest listing, with edges rounded to whole dollars.
+    pub fn log(prices: &Vec<u64>, n: usize) -> PriceRanges {
+        let low = prices.iter().copied().filter(|p| *p > 0).min().unwrap_or(100).max(100) as f64;
+        let high = prices.iter().copied().max().unwrap_or(0) as f64;
+        let mut edges:Vec<u64> = Vec::new();
+        if high > low {
+            for i in 1..n {
+                let edge = low * (high / low).powf(i as f64 / n as f64);
+                edges.push((edge / 100.0).round() as u64 * 100);
+            }
+        }
+        PriceRanges::from_edges(edges)
+    }
+
+    // "fixed", "quantile:N", "log:N" or custom dollar edges such as "75,150,250,1000".
+    // quantile and log ranges are fitted on the prices in `train_vec`. ranges
+    // that leave a single class, such as "0" or quantiles over one repeated
+    // price, are an error: every listing would be predicted right.
+    pub fn from_spec(spec: &str, train_vec: &Vec<RoomInfo>) -> Result<PriceRanges,String> {
+        let ranges = PriceRanges::parse_spec(spec, train_vec)?;
+        if ranges.labels.len() < 2 { return Err(format!("{:?} leaves a single price range", spec)); }
+        return Ok(ranges);
+    }
+
+    fn parse_spec(spec: &str, train_vec: &Vec<RoomInfo>) -> Result<PriceRanges,String> {
+        let prices:Vec<u64> = train_vec.iter().filter_map(|r| r.price_cents).collect();
+        let count = |n: &str| -> Result<usize,String> {
+            match n.parse::<usize>() {
+                Ok(n) if n >= 2 => Ok(n),
+                _ => Err(format!("expected at least 2 ranges, got {:?}", n))
+            }
+        };
+        if spec == "fixed" {
+            return Ok(PriceRanges::fixed());
+        } else if let Some(n) = spec.strip_prefix("quantile:") {
+            return Ok(PriceRanges::quantile(&prices, count(n)?));
+        } else if let Some(n) = spec.strip_prefix("log:") {
+            return Ok(PriceRanges::log(&prices, count(n)?));
+        }
+        let mut edges:Vec<u64> = Vec::new();
+        for edge in spec.split(",") {
+            edges.push(parse_price_cents(edge).map_err(|e| format!("bad range edge {:?}: {}", edge, e))?);
+        }
+        return Ok(PriceRanges::from_edges(edges));
+    }
+
+    pub fn class_of(&self, cents: u64) -> usize {
+        self.edges.iter().filter(|e| cents >= **e).count()
+    }
+
+    // fill in the class of every listing from its price
+    pub fn assign(&self, node_vec: &mut Vec<RoomInfo>) {
+        for roominfo in node_vec {
+            roominfo.price = roominfo.price_cents.map(|cents| self.class_of(cents));
+        }
+    }
+}
+
+fn dollars(cents: u64) -> String {
+    if cents.is_multiple_of(100) { format!("{}", cents / 100) } else { format!("{}.{:02}", cents / 100, cents % 100) }
+}
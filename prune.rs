@@ -0,0 +1,325 @@
+// post-pruning of a built classification tree. every method works bottom-up,
+// turning internal nodes back into leaves that predict their training
+// majority, and compact() then drops the nodes that are no longer reachable.
+use crate::{DecisionTreeNode, RoomInfo, evaluate, majority_class};
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Pruning {
+    // against the held-out verify rows
+    ReducedError,
+    // C4.5 error-based pruning with this confidence factor
+    Pessimistic(f64),
+    // CART weakest-link pruning at this alpha; None picks the alpha on the
+    // path with the best verify accuracy
+    CostComplexity(Option<f64>),
+}
+
+impl Pruning {
+    // "reduced-error", "pessimistic[:CF]" or "cost-complexity[:ALPHA]"
+    pub fn from_spec(spec: &str) -> Result<Pruning,String> {
+        let (name,value) = match spec.split_once(":") {
+            Some((name,value)) => (name, Some(value)),
+            None => (spec, None)
+        };
+        let number = |v: &str| -> Result<f64,String> {
+            match v.parse::<f64>() {
+                Ok(n) if n >= 0.0 && n.is_finite() => Ok(n),
+                _ => Err(format!("expected a non-negative number, got {:?}", v))
+            }
+        };
+        match (name,value) {
+            ("reduced-error",None) => Ok(Pruning::ReducedError),
+            ("pessimistic",None) => Ok(Pruning::Pessimistic(0.25)),
+            ("pessimistic",Some(cf)) => match number(cf)? {
+                cf if cf > 0.0 && cf < 1.0 => Ok(Pruning::Pessimistic(cf)),
+                _ => Err(format!("confidence factor must be between 0 and 1, got {}", cf))
+            },
+            ("cost-complexity",None) => Ok(Pruning::CostComplexity(None)),
+            ("cost-complexity",Some(alpha)) => Ok(Pruning::CostComplexity(Some(number(alpha)?))),
+            _ => Err(format!("unknown method {:?}, expected reduced-error, pessimistic[:CF] or cost-complexity[:ALPHA]", spec))
+        }
+    }
+
+    // reduced-error and the automatic cost-complexity alpha are judged on held-out rows
+    pub fn needs_verify(&self) -> bool {
+        matches!(self, Pruning::ReducedError | Pruning::CostComplexity(None))
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Pruning::ReducedError => String::from("reduced-error"),
+            Pruning::Pessimistic(cf) => format!("pessimistic:{}", cf),
+            Pruning::CostComplexity(None) => String::from("cost-complexity"),
+            Pruning::CostComplexity(Some(alpha)) => format!("cost-complexity:{}", alpha),
+        }
+    }
+}
+
+// prune the tree rooted at the last node and return the compacted result
+pub fn prune(mut tree_vec: Vec<DecisionTreeNode>, pruning: &Pruning, verify_vec: &Vec<RoomInfo>, labels: &Vec<String>) -> Vec<DecisionTreeNode> {
+    let root = tree_vec.len()-1;
+    match pruning {
+        Pruning::ReducedError => { reduced_error(&mut tree_vec, root, verify_vec.iter().map(|r| (r,r.weight)).collect(), labels); },
+        Pruning::Pessimistic(cf) => { pessimistic(&mut tree_vec, root, *cf, labels); },
+        Pruning::CostComplexity(alpha) => {
+            let path = cost_complexity_path(&tree_vec, labels);
+            let chosen = match alpha {
+                // the last step whose alpha does not exceed the requested one
+                Some(alpha) => path.iter().rposition(|(a,_)| *a <= *alpha).unwrap_or(0),
+                None => best_on_verify(&path, verify_vec, labels),
+            };
+            return path.into_iter().nth(chosen).map(|(_,tree)| tree).unwrap_or(tree_vec);
+        }
+    }
+    return compact(&tree_vec, root);
+}
+
+// copy the nodes reachable from `root_id` into a new arena, children before
+// parents so the root is again the last node
+pub fn compact(tree_vec: &Vec<DecisionTreeNode>, root_id: usize) -> Vec<DecisionTreeNode> {
+    let mut compacted:Vec<DecisionTreeNode> = Vec::new();
+    copy_subtree(tree_vec, root_id, &mut compacted);
+    return compacted;
+}
+
+fn copy_subtree(tree_vec: &Vec<DecisionTreeNode>, id: usize, compacted: &mut Vec<DecisionTreeNode>) -> usize {
+    let mut node = tree_vec[id].clone();
+    node.children = tree_vec[id].children.iter().map(|c| copy_subtree(tree_vec, *c, compacted)).collect();
+    compacted.push(node);
+    return compacted.len()-1;
+}
+
+fn make_leaf(node: &mut DecisionTreeNode, labels: &Vec<String>) {
+    let class = node.class.or(majority_class(&node.class_dist)).unwrap_or(0);
+    node.attribute = labels[class].clone();
+    node.class = Some(class);
+    node.split = None;
+    node.children.clear();
+}
+
+// (training rows, training rows misclassified) at `node` if it were a leaf
+fn training_errors(node: &DecisionTreeNode) -> (f64,f64) {
+    let n:f64 = node.class_dist.iter().sum();
+    let class = match node.class.or(majority_class(&node.class_dist)) {
+        Some(class) => class,
+        None => return (n, 0.0)
+    };
+    return (n, n - node.class_dist[class]);
+}
+
+// a node becomes a leaf when that makes no more mistakes on the verify rows
+// reaching it than its subtree does. rows missing a split's value go down every
+// branch with a share of their weight, as in search_tree. returns the
+// mistakes left in the subtree.
+fn reduced_error(tree_vec: &mut Vec<DecisionTreeNode>, id: usize, rows: Vec<(&RoomInfo,f64)>, labels: &Vec<String>) -> f64 {
+    let class = tree_vec[id].class.or(majority_class(&tree_vec[id].class_dist));
+    let as_leaf:f64 = rows.iter().filter(|(r,_)| r.price.is_some() && r.price != class).map(|(_,w)| w).sum();
+    let attribute = match tree_vec[id].split.clone() {
+        Some(attribute) => attribute,
+        None => return as_leaf
+    };
+    let children = tree_vec[id].children.clone();
+    let mut parts:Vec<Vec<(&RoomInfo,f64)>> = vec![Vec::new();children.len()];
+    for (roominfo,weight) in rows {
+        match attribute.value(roominfo) {
+            Some(value) => parts[value].push((roominfo,weight)),
+            None => for (child,part) in children.iter().zip(parts.iter_mut()) {
+                let share = tree_vec[*child].weight / tree_vec[id].weight;
+                if share > 0.0 { part.push((roominfo,weight * share)); }
+            }
+        }
+    }
+    let mut subtree:f64 = 0.0;
+    for (child,part) in children.into_iter().zip(parts) {
+        subtree += reduced_error(tree_vec, child, part, labels);
+    }
+    // shares of a split weight add up to it only up to a rounding error, so a
+    // tie still goes to the leaf
+    if as_leaf <= subtree + 1e-9 * subtree.max(1.0) {
+        make_leaf(&mut tree_vec[id], labels);
+        return as_leaf;
+    }
+    return subtree;
+}
+
+// C4.5 estimates the true error of a leaf by the upper limit of a binomial
+// confidence interval on its training errors, and replaces a subtree by a leaf
+// when the leaf's estimate is no worse. returns the estimated errors.
+fn pessimistic(tree_vec: &mut Vec<DecisionTreeNode>, id: usize, cf: f64, labels: &Vec<String>) -> f64 {
+    let (n,errors) = training_errors(&tree_vec[id]);
+    let as_leaf = errors + added_errors(n, errors, cf);
+    if tree_vec[id].split.is_none() { return as_leaf; }
+    let mut subtree:f64 = 0.0;
+    for child in tree_vec[id].children.clone() {
+        subtree += pessimistic(tree_vec, child, cf, labels);
+    }
+    // the same small tolerance as C4.5, favouring the simpler tree
+    if as_leaf <= subtree + 0.1 {
+        make_leaf(&mut tree_vec[id], labels);
+        return as_leaf;
+    }
+    return subtree;
+}
+
+// extra errors to expect on top of the `e` observed in `n` rows, following
+// Quinlan's addErrs: exact for no errors, interpolated below one, and the
+// normal approximation above.
+fn added_errors(n: f64, e: f64, cf: f64) -> f64 {
+    if n == 0.0 { return 0.0; }
+    if e < 1e-6 { return n * (1.0 - (cf.ln() / n).exp()); }
+    if e < 0.9999 {
+        let none = n * (1.0 - (cf.ln() / n).exp());
+        return none + e * (added_errors(n, 1.0, cf) - none);
+    }
+    if e + 0.5 >= n { return 0.67 * (n - e); }
+    let z = normal_deviate(cf);
+    let coeff = z * z;
+    let upper = (e + 0.5 + coeff / 2.0 + (coeff * ((e + 0.5) * (1.0 - (e + 0.5) / n) + coeff / 4.0)).sqrt()) / (n + coeff);
+    return n * upper - e;
+}
+
+// one-sided standard normal deviate for `cf`, interpolated in C4.5's table
+fn normal_deviate(cf: f64) -> f64 {
+    let val = [0.0, 0.001, 0.005, 0.01, 0.05, 0.10, 0.20, 0.40, 1.00];
+    let dev = [4.0, 3.09, 2.58, 2.33, 1.65, 1.28, 0.84, 0.25, 0.00];
+    let mut i = 1;
+    while i < val.len() - 1 && cf > val[i] { i += 1; }
+    return dev[i-1] + (dev[i] - dev[i-1]) * (cf - val[i-1]) / (val[i] - val[i-1]);
+}
+
+// the nested subtrees of weakest-link pruning, each with the smallest alpha at
+// which it is optimal: the first keeps every split that lowers the training
+// error, the last is the root alone. errors are fractions of the training rows.
+pub fn cost_complexity_path(tree_vec: &Vec<DecisionTreeNode>, labels: &Vec<String>) -> Vec<(f64,Vec<DecisionTreeNode>)> {
+    let mut tree = compact(tree_vec, tree_vec.len()-1);
+    let total:f64 = tree[tree.len()-1].class_dist.iter().sum::<f64>().max(1.0);
+    let mut path:Vec<(f64,Vec<DecisionTreeNode>)> = Vec::new();
+    let mut alpha:f64 = 0.0;
+    loop {
+        let root = tree.len()-1;
+        weakest_links(&mut tree, root, alpha, total, labels);
+        tree = compact(&tree, root);
+        path.push((alpha, tree.clone()));
+        let root = tree.len()-1;
+        if tree[root].split.is_none() { break; }
+        alpha = link_strength(&tree, root, total).2;
+    }
+    return path;
+}
+
+// (errors of the subtree, its leaves, the weakest link g(t) inside it), where
+// g(t) is the training error added per leaf removed by pruning node t
+fn link_strength(tree_vec: &Vec<DecisionTreeNode>, id: usize, total: f64) -> (f64,f64,f64) {
+    let (_,errors) = training_errors(&tree_vec[id]);
+    if tree_vec[id].split.is_none() { return (errors / total, 1.0, f64::INFINITY); }
+    let (mut subtree,mut leaves,mut weakest) = (0.0,0.0,f64::INFINITY);
+    for child in &tree_vec[id].children {
+        let (e,l,w) = link_strength(tree_vec, *child, total);
+        subtree += e;
+        leaves += l;
+        weakest = weakest.min(w);
+    }
+    let g = (errors / total - subtree) / (leaves - 1.0).max(1.0);
+    return (subtree, leaves, weakest.min(g));
+}
+
+// prune every node whose link is no stronger than `alpha`, children first so
+// a parent is judged on what is left below it. returns (errors, leaves).
+fn weakest_links(tree_vec: &mut Vec<DecisionTreeNode>, id: usize, alpha: f64, total: f64, labels: &Vec<String>) -> (f64,f64) {
+    let (_,errors) = training_errors(&tree_vec[id]);
+    if tree_vec[id].split.is_none() { return (errors / total, 1.0); }
+    let (mut subtree,mut leaves) = (0.0,0.0);
+    for child in tree_vec[id].children.clone() {
+        let (e,l) = weakest_links(tree_vec, child, alpha, total, labels);
+        subtree += e;
+        leaves += l;
+    }
+    let g = (errors / total - subtree) / (leaves - 1.0).max(1.0);
+    if g <= alpha + 1e-12 {
+        make_leaf(&mut tree_vec[id], labels);
+        return (errors / total, 1.0);
+    }
+    return (subtree, leaves);
+}
+
+// index of the step with the best verify accuracy, ties going to the smaller tree
+fn best_on_verify(path: &Vec<(f64,Vec<DecisionTreeNode>)>, verify_vec: &Vec<RoomInfo>, labels: &Vec<String>) -> usize {
+    let mut best:usize = 0;
+    let mut max:f64 = f64::NEG_INFINITY;
+    for (i,(_,tree)) in path.iter().enumerate() {
+        let accuracy = evaluate(tree, tree.len()-1, verify_vec, labels).accuracy();
+        if accuracy >= max { max = accuracy; best = i; }
+    }
+    return best;
+}
+
+pub fn print_path(path: &Vec<(f64,Vec<DecisionTreeNode>)>, verify_vec: &Vec<RoomInfo>, labels: &Vec<String>) {
+    println!("Cost-complexity path (accuracy on the rows held out for pruning):");
+    println!("{:>12}{:>8}{:>10}", "alpha", "leaves", "accuracy");
+    for (alpha,tree) in path {
+        let leaves = tree.iter().filter(|n| n.split.is_none()).count();
+        let accuracy = evaluate(tree, tree.len()-1, verify_vec, labels).accuracy();
+        println!("{:>12.6}{:>8}{:>10.4}", alpha, leaves, accuracy);
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_tree;
+    use crate::price::PriceRanges;
+    use crate::tests::{fitted, listings};
+
+    // listings where every fifth one, counted from `shift`, has an odd price,
+    // so a fully grown tree overfits them
+    fn noisy(n: usize, shift: usize) -> Vec<RoomInfo> {
+        let mut rows = listings(n);
+        for (i,roominfo) in rows.iter_mut().enumerate() {
+            if (i + shift).is_multiple_of(5) { roominfo.price_cents = Some(45_000); }
+        }
+        PriceRanges::fixed().assign(&mut rows);
+        return rows;
+    }
+
+    fn grown() -> (Vec<DecisionTreeNode>,Vec<String>) {
+        let (_,config) = fitted(60);
+        return (build_tree(noisy(60, 0), &config), config.labels);
+    }
+
+    #[test]
+    fn cost_complexity_path_ends_at_the_root() {
+        let (tree_vec,labels) = grown();
+        let path = cost_complexity_path(&tree_vec, &labels);
+        assert!(path.len() > 1);
+        assert!(path.windows(2).all(|w| w[0].0 <= w[1].0));
+        assert!(path.windows(2).all(|w| w[0].1.len() > w[1].1.len()));
+        let (_,last) = path.last().unwrap();
+        assert_eq!(last.len(), 1);
+        assert!(last[0].split.is_none());
+    }
+
+    #[test]
+    fn reduced_error_never_adds_errors_on_the_pruning_rows() {
+        let (tree_vec,labels) = grown();
+        for shift in 1..5 {
+            let rows = noisy(60, shift);
+            let before = evaluate(&tree_vec, tree_vec.len()-1, &rows, &labels).accuracy();
+            let pruned = prune(tree_vec.clone(), &Pruning::ReducedError, &rows, &labels);
+            let after = evaluate(&pruned, pruned.len()-1, &rows, &labels).accuracy();
+            assert!(after >= before - 1e-9, "shift {}: {} < {}", shift, after, before);
+            assert!(pruned.len() <= tree_vec.len());
+        }
+    }
+
+    #[test]
+    fn pessimistic_errors_grow_as_confidence_falls() {
+        assert!(added_errors(10.0, 0.0, 0.25) > 0.0);
+        assert!(added_errors(10.0, 2.0, 0.1) > added_errors(10.0, 2.0, 0.25));
+        assert!(normal_deviate(0.1) > normal_deviate(0.25));
+        let (tree_vec,labels) = grown();
+        let strict = prune(tree_vec.clone(), &Pruning::Pessimistic(0.01), &Vec::new(), &labels);
+        let loose = prune(tree_vec.clone(), &Pruning::Pessimistic(0.5), &Vec::new(), &labels);
+        assert!(strict.len() <= loose.len() && loose.len() <= tree_vec.len());
+    }
+}
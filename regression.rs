@@ -0,0 +1,245 @@
+// regression trees: the same arena of DecisionTreeNode and the same attributes
+// as make_tree, but every node predicts a nightly price in dollars and splits
+// are chosen by how much they reduce the squared or absolute error. prices are
+// handled as (price, weight) pairs so rows split by a missing value count in part.
+// a row's `target`, when set, is fitted instead of its price.
+use std::rc::Rc;
+use crate::attribute::{Attribute, NumericFeature, ThresholdAttribute};
+use crate::{DecisionTreeNode, RoomInfo, TreeConfig, min_weight, partition};
+
+#[derive(Debug)]
+pub enum Loss {
+    Squared,  // variance reduction, mean at the leaves
+    Absolute, // MAE reduction, median at the leaves
+}
+
+pub fn price(roominfo: &RoomInfo) -> f64 {
+    roominfo.price_cents.unwrap_or(0) as f64 / 100.0
+}
+
+// what the tree is fitted to
+fn target(roominfo: &RoomInfo) -> f64 {
+    roominfo.target.unwrap_or_else(|| price(roominfo))
+}
+
+fn weighted_price(roominfo: &RoomInfo) -> (f64,f64) {
+    (target(roominfo), roominfo.weight)
+}
+
+// weighted median; when the weight splits exactly in half between two prices
+// it is their midpoint, the usual median of an even count.
+fn median(prices: &mut Vec<(f64,f64)>) -> f64 {
+    if prices.is_empty() { return 0.0; }
+    prices.sort_by(|a,b| a.0.total_cmp(&b.0));
+    let half = prices.iter().map(|p| p.1).sum::<f64>() / 2.0;
+    let mut below:f64 = 0.0;
+    for i in 0..prices.len() {
+        below += prices[i].1;
+        if (below - half).abs() <= 1e-12 * half && i + 1 < prices.len() { return (prices[i].0 + prices[i+1].0) / 2.0; }
+        if below > half { return prices[i].0; }
+    }
+    return prices[prices.len()-1].0;
+}
+
+// the constant a leaf predicts for these prices
+fn leaf_value(prices: &mut Vec<(f64,f64)>, loss: &Loss) -> f64 {
+    let weight:f64 = prices.iter().map(|p| p.1).sum();
+    match loss {
+        Loss::Squared => if weight == 0.0 { 0.0 } else { prices.iter().map(|(p,w)| w * p).sum::<f64>() / weight },
+        Loss::Absolute => median(prices),
+    }
+}
+
+// total error of predicting leaf_value for every price
+fn node_loss(prices: &mut Vec<(f64,f64)>, loss: &Loss) -> f64 {
+    let value = leaf_value(prices, loss);
+    match loss {
+        Loss::Squared => prices.iter().map(|(p,w)| w * (p - value) * (p - value)).sum(),
+        Loss::Absolute => prices.iter().map(|(p,w)| w * (p - value).abs()).sum(),
+    }
+}
+
+// build a regression tree on `node_vec`; the root is the last node.
+pub fn build_regression_tree(node_vec: Vec<RoomInfo>, config: &TreeConfig, loss: &Loss) -> Vec<DecisionTreeNode> {
+    let mut tree_vec:Vec<DecisionTreeNode> = Vec::new();
+    let mut root:DecisionTreeNode = DecisionTreeNode::new();
+    make_regression_tree(&mut tree_vec,&mut root, node_vec, 0.0, 0, config, loss);
+    tree_vec.push(root);
+    return tree_vec;
+}
+
+// `fallback` is the parent's prediction, used by an empty partition. the
+// stopping rules of `config` apply as in make_tree, with min_impurity_decrease
+// measured as the drop in error per row.
+fn make_regression_tree(tree_vec: &mut Vec<DecisionTreeNode>, node: &mut DecisionTreeNode, node_vec: Vec<RoomInfo>, fallback: f64, depth: usize, config: &TreeConfig, loss: &Loss) {
+    let mut prices:Vec<(f64,f64)> = node_vec.iter().map(weighted_price).collect();
+    let value = if prices.is_empty() { fallback } else { leaf_value(&mut prices, loss) };
+    node.value = Some(value);
+    node.weight = node_vec.iter().fold(0.0, |w,r| w + r.weight);
+    let stop = node_vec.len() < 2 || node.weight < min_weight(config.min_samples_split) || config.max_depth.is_some_and(|d| depth >= d);
+    let choice = if stop { None } else { chose_regression_split(&node_vec, config, loss) };
+    let attribute = match choice {
+        Some(attribute) => attribute,
+        None => {
+            node.attribute = format!("${:.2}", value);
+            return;
+        }
+    };
+
+    node.attribute = attribute.name();
+    for nodevec in partition(attribute.as_ref(), node_vec) {
+        let mut child: DecisionTreeNode = DecisionTreeNode::new();
+        make_regression_tree(tree_vec,&mut child,nodevec,value,depth+1,config,loss);
+        tree_vec.push(child);
+        node.children.push(tree_vec.len()-1);
+    }
+    node.split = Some(attribute);
+}
+
+// the attribute or threshold that lowers the error the most, or None when
+// nothing lowers the error of the node itself. as with chose_attribute the
+// reduction is measured on the rows with a known value and scaled by their share.
+fn chose_regression_split(node_vec: &Vec<RoomInfo>, config: &TreeConfig, loss: &Loss) -> Option<Rc<dyn Attribute>> {
+    let mut prices:Vec<(f64,f64)> = node_vec.iter().map(weighted_price).collect();
+    let parent_loss = node_loss(&mut prices, loss);
+    let weight:f64 = node_vec.iter().map(|r| r.weight).sum();
+    let min_leaf = min_weight(config.min_samples_leaf);
+
+    let mut best:Option<Rc<dyn Attribute>> = None;
+    // ignore reductions that are only floating point noise
+    let mut best_reduction:f64 = 1e-9 * parent_loss.max(1.0);
+    for attribute in &config.attributes {
+        let mut parts:Vec<Vec<(f64,f64)>> = vec![Vec::new();attribute.num_values()];
+        for roominfo in node_vec {
+            if let Some(value) = attribute.value(roominfo) { parts[value].push(weighted_price(roominfo)); }
+        }
+        if parts.iter().filter(|p| !p.is_empty()).count() < 2 { continue; }
+        if parts.iter().any(|p| !p.is_empty() && p.iter().map(|v| v.1).sum::<f64>() < min_leaf) { continue; }
+        let mut known:Vec<(f64,f64)> = parts.concat();
+        let known_weight:f64 = known.iter().map(|v| v.1).sum();
+        let split_loss:f64 = parts.iter_mut().map(|p| node_loss(p, loss)).sum();
+        let reduction = known_weight / weight * (node_loss(&mut known, loss) - split_loss);
+        if reduction > best_reduction { best_reduction = reduction; best = Some(attribute.clone()); }
+    }
+    for feature in &config.features {
+        if let Some((threshold,reduction)) = best_regression_threshold(node_vec, feature, min_leaf, loss) {
+            if reduction > best_reduction {
+                best_reduction = reduction;
+                best = Some(Rc::new(ThresholdAttribute { feature: *feature, threshold }));
+            }
+        }
+    }
+    if best_reduction / weight < config.min_impurity_decrease { return None; }
+    return best;
+}
+
+// the `<= v` cut with the lowest total error over the rows with a known value,
+// returned with its scaled reduction. squared error is scanned in one pass with
+// running sums; absolute error recomputes both medians per cut.
+fn best_regression_threshold(node_vec: &Vec<RoomInfo>, feature: &NumericFeature, min_leaf: f64, loss: &Loss) -> Option<(f64,f64)> {
+    let mut values:Vec<(f64,f64,f64)> = node_vec.iter().filter_map(|r| (feature.get)(r).map(|v| (v, target(r), r.weight))).collect();
+    values.sort_by(|a,b| a.0.total_cmp(&b.0));
+    let n = values.len();
+    let weight:f64 = node_vec.iter().map(|r| r.weight).sum();
+
+    let (total_weight,total_sum,total_sq) = values.iter().fold((0.0,0.0,0.0), |(n,s,q),(_,p,w)| (n + w, s + w * p, q + w * p * p));
+    let (mut left_weight,mut left_sum,mut left_sq) = (0.0,0.0,0.0);
+    let mut best:Option<(f64,f64)> = None;
+    for i in 0..n.saturating_sub(1) {
+        let (value,p,w) = values[i];
+        left_weight += w;
+        left_sum += w * p;
+        left_sq += w * p * p;
+        if values[i+1].0 == value { continue; }
+        if left_weight < min_leaf || total_weight - left_weight < min_leaf { continue; }
+        let split_loss = match loss {
+            Loss::Squared => {
+                let right_weight = total_weight - left_weight;
+                let (right_sum,right_sq) = (total_sum - left_sum,total_sq - left_sq);
+                (left_sq - left_sum * left_sum / left_weight) + (right_sq - right_sum * right_sum / right_weight)
+            },
+            Loss::Absolute => {
+                let mut left:Vec<(f64,f64)> = values[..=i].iter().map(|v| (v.1,v.2)).collect();
+                let mut right:Vec<(f64,f64)> = values[i+1..].iter().map(|v| (v.1,v.2)).collect();
+                node_loss(&mut left, loss) + node_loss(&mut right, loss)
+            }
+        };
+        if best.is_none_or(|(_,l)| split_loss < l) { best = Some((value,split_loss)); }
+    }
+    let mut known:Vec<(f64,f64)> = values.iter().map(|v| (v.1,v.2)).collect();
+    let known_loss = node_loss(&mut known, loss);
+    return best.map(|(threshold,split_loss)| (threshold, total_weight / weight * (known_loss - split_loss)));
+}
+
+// a listing missing a split's value gets the average of every branch,
+// weighted by the branches' share of the training weight
+pub fn predict_price(tree_vec: &Vec<DecisionTreeNode>, id: usize, roominfo: &RoomInfo) -> f64 {
+    let node = &tree_vec[id];
+    let attribute = match &node.split {
+        Some(attribute) => attribute,
+        None => return node.value.unwrap_or(0.0)
+    };
+    match attribute.value(roominfo) {
+        Some(value) => predict_price(tree_vec, node.children[value], roominfo),
+        None => node.children.iter().map(|c| tree_vec[*c].weight / node.weight * predict_price(tree_vec, *c, roominfo)).sum()
+    }
+}
+
+// errors in dollars, averaged by listing weight. MAPE leaves out listings
+// priced at $0, whose percentage error is undefined.
+pub struct RegressionReport {
+    count: usize,
+    weight: f64,
+    squared: f64,
+    absolute: f64,
+    percentage: f64,
+    percentage_count: usize,
+    percentage_weight: f64,
+}
+
+impl RegressionReport {
+    pub fn rmse(&self) -> f64 {
+        if self.weight == 0.0 { 0.0 } else { (self.squared / self.weight).sqrt() }
+    }
+
+    pub fn mae(&self) -> f64 {
+        if self.weight == 0.0 { 0.0 } else { self.absolute / self.weight }
+    }
+
+    pub fn mape(&self) -> f64 {
+        if self.percentage_weight == 0.0 { 0.0 } else { 100.0 * self.percentage / self.percentage_weight }
+    }
+
+    pub fn print(&self) {
+        println!("Evaluated {} listings", self.count);
+        if self.weight != self.count as f64 { println!("Total weight: {}", self.weight); }
+        println!("RMSE: ${:.2}", self.rmse());
+        println!("MAE:  ${:.2}", self.mae());
+        println!("MAPE: {:.2}% (over {} listings with a non-zero price)", self.mape(), self.percentage_count);
+    }
+}
+
+pub fn evaluate_regression(tree_vec: &Vec<DecisionTreeNode>, root_id: usize, verify_vec: &Vec<RoomInfo>) -> RegressionReport {
+    score_regression(verify_vec, |roominfo| predict_price(tree_vec, root_id, roominfo))
+}
+
+// compare what any model predicts with the listings' prices
+pub fn score_regression<F: Fn(&RoomInfo) -> f64>(verify_vec: &Vec<RoomInfo>, predict: F) -> RegressionReport {
+    let mut report = RegressionReport { count: 0, weight: 0.0, squared: 0.0, absolute: 0.0, percentage: 0.0, percentage_count: 0, percentage_weight: 0.0 };
+    for roominfo in verify_vec {
+        if roominfo.price_cents.is_none() { continue; }
+        let actual = price(roominfo);
+        let error = predict(roominfo) - actual;
+        let w = roominfo.weight;
+        report.count += 1;
+        report.weight += w;
+        report.squared += w * error * error;
+        report.absolute += w * error.abs();
+        if actual > 0.0 {
+            report.percentage += w * error.abs() / actual;
+            report.percentage_count += 1;
+            report.percentage_weight += w;
+        }
+    }
+    return report;
+}
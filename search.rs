@@ -0,0 +1,105 @@
+// hyperparameter search: score many Settings with cross-validation and rank
+// them. the criterion, depth and leaf size are searched; every other setting,
+// the binning included, comes from the command line unless the binnings are
+// listed too. accuracy over 3 price ranges says nothing about accuracy over 6,
+// so settings are only ranked against others with the same binning.
+use crate::{RoomInfo, Settings};
+use crate::criterion::Criterion;
+use crate::cv::{CvPlan, CvResult, cross_validate};
+use crate::rng::Rng;
+
+const CRITERIA: [Criterion; 4] = [Criterion::InfoGain, Criterion::GainRatio, Criterion::Gini, Criterion::ChiSquare];
+// the binnings `--search-bins=all` tries
+pub const BINS: [&str; 4] = ["fixed", "quantile:3", "quantile:5", "log:4"];
+const DEPTHS: [Option<usize>; 5] = [None, Some(2), Some(3), Some(4), Some(6)];
+const LEAF_SIZES: [usize; 4] = [1, 2, 3, 5];
+
+pub enum Search {
+    // every combination of the lists above
+    Grid,
+    // this many draws, with depth, leaf size and min_impurity_decrease taken
+    // from ranges rather than the lists
+    Random(usize),
+}
+
+impl Search {
+    // "grid" or "random:N"
+    pub fn from_spec(spec: &str) -> Result<Search,String> {
+        if spec == "grid" { return Ok(Search::Grid); }
+        match spec.strip_prefix("random:").map(|n| n.parse::<usize>()) {
+            Some(Ok(n)) if n > 0 => Ok(Search::Random(n)),
+            _ => Err(format!("expected grid or random:N, got {:?}", spec))
+        }
+    }
+}
+
+fn candidates(base: &Settings, search: &Search, binnings: &Vec<String>, seed: u64) -> Vec<Settings> {
+    let mut settings:Vec<Settings> = Vec::new();
+    match search {
+        Search::Grid => {
+            for criterion in CRITERIA {
+                for bins in binnings {
+                    for max_depth in DEPTHS {
+                        for min_samples_leaf in LEAF_SIZES {
+                            settings.push(Settings { bins: bins.clone(), criterion, max_depth, min_samples_leaf, ..base.clone() });
+                        }
+                    }
+                }
+            }
+        },
+        Search::Random(n) => {
+            let mut rng = Rng::new(seed);
+            for _ in 0..*n {
+                let depth = rng.below(9);
+                settings.push(Settings {
+                    bins: binnings[rng.below(binnings.len())].clone(),
+                    criterion: CRITERIA[rng.below(CRITERIA.len())],
+                    // 0 stands for no limit
+                    max_depth: if depth == 0 { None } else { Some(depth) },
+                    min_samples_leaf: 1 + rng.below(5),
+                    min_impurity_decrease: (rng.next_f64() * 0.05 * 1e4).round() / 1e4,
+                    ..base.clone()
+                });
+            }
+        }
+    }
+    return settings;
+}
+
+// cross-validate every candidate with each of `binnings`, best mean accuracy
+// first and ties broken by macro F1
+pub fn run_search(listings: &Vec<RoomInfo>, base: &Settings, search: &Search, binnings: &Vec<String>, plan: &CvPlan) -> Result<Vec<(Settings,CvResult)>,String> {
+    let mut results:Vec<(Settings,CvResult)> = Vec::new();
+    for settings in candidates(base, search, binnings, plan.seed) {
+        let result = cross_validate(listings, &settings, plan)?;
+        results.push((settings,result));
+    }
+    results.sort_by(|a,b| {
+        let (a,b) = (&a.1,&b.1);
+        b.accuracy().0.total_cmp(&a.accuracy().0).then(b.macro_f1().0.total_cmp(&a.macro_f1().0))
+    });
+    return Ok(results);
+}
+
+// one leaderboard per binning, in the order of `binnings`
+pub fn print_leaderboard(results: &Vec<(Settings,CvResult)>, binnings: &Vec<String>, top: usize) {
+    println!("Evaluated {} settings", results.len());
+    for bins in binnings {
+        let ranked:Vec<&(Settings,CvResult)> = results.iter().filter(|(s,_)| &s.bins == bins).collect();
+        if binnings.len() > 1 {
+            if ranked.is_empty() { continue; }
+            println!();
+            println!("--bins={}", bins);
+        }
+        println!("{:>5}{:>20}{:>20}  settings", "rank", "accuracy", "macro f1");
+        for (rank,(settings,result)) in ranked.iter().take(top).enumerate() {
+            let (acc,acc_std) = result.accuracy();
+            let (f1,f1_std) = result.macro_f1();
+            println!("{:>5}{:>11.4} +/-{:.4}{:>11.4} +/-{:.4}  {}", rank + 1, acc, acc_std, f1, f1_std, settings.options());
+        }
+        if let Some((best,_)) = ranked.first() {
+            println!();
+            println!("Best: {}", best.options());
+        }
+    }
+}
@@ -0,0 +1,120 @@
+// how the priced listings are divided into train_vec and verify_vec. scraped
+// files are sorted by neighbourhood and host, so every method draws the held
+// out rows at random from a seed, which keeps runs repeatable.
+use std::collections::BTreeMap;
+use crate::RoomInfo;
+use crate::price::PriceRanges;
+use crate::rng::Rng;
+
+pub enum Split {
+    // a plain shuffle
+    Shuffled,
+    // the same share of every price class
+    Stratified,
+    // whole hosts at a time, so no host is on both sides
+    Grouped,
+}
+
+impl Split {
+    pub fn from_name(name: &str) -> Option<Split> {
+        match name {
+            "shuffle" => Some(Split::Shuffled),
+            "stratified" => Some(Split::Stratified),
+            "group" => Some(Split::Grouped),
+            _ => None
+        }
+    }
+}
+
+// (train_vec, verify_vec) with about `ratio` of the listings held out.
+// `strata` holds the price class of every listing for stratified splits, and
+// a listing without a host id is a group of its own; grouping a file with no
+// host ids at all is an error. listings keep their file order on both sides.
+pub fn split(listings: Vec<RoomInfo>, method: &Split, ratio: f64, seed: u64, strata: &Vec<usize>) -> Result<(Vec<RoomInfo>,Vec<RoomInfo>),String> {
+    let mut rng = Rng::new(seed);
+    let mut held_out:Vec<bool> = vec![false;listings.len()];
+    match method {
+        Split::Shuffled => hold_out(&mut held_out, (0..listings.len()).collect(), ratio, &mut rng),
+        Split::Stratified => {
+            let mut classes:BTreeMap<usize,Vec<usize>> = BTreeMap::new();
+            for (i,class) in strata.iter().enumerate() {
+                classes.entry(*class).or_default().push(i);
+            }
+            for (_,indices) in classes {
+                hold_out(&mut held_out, indices, ratio, &mut rng);
+            }
+        },
+        Split::Grouped => {
+            if listings.iter().all(|r| r.host_id.is_none()) {
+                return Err(String::from("no listing has a host_id, so there is nothing to group by"));
+            }
+            let mut hosts:BTreeMap<&str,Vec<usize>> = BTreeMap::new();
+            let mut groups:Vec<Vec<usize>> = Vec::new();
+            for (i,roominfo) in listings.iter().enumerate() {
+                match &roominfo.host_id {
+                    Some(host) => hosts.entry(host.as_str()).or_default().push(i),
+                    None => groups.push(vec![i])
+                }
+            }
+            groups.extend(hosts.into_values());
+            rng.shuffle(&mut groups);
+            let target = (ratio * listings.len() as f64).round() as usize;
+            let mut count:usize = 0;
+            for group in groups {
+                if count >= target { break; }
+                count += group.len();
+                for i in group { held_out[i] = true; }
+            }
+        }
+    }
+
+    let mut train_vec:Vec<RoomInfo> = Vec::new();
+    let mut verify_vec:Vec<RoomInfo> = Vec::new();
+    for (roominfo,held) in listings.into_iter().zip(held_out) {
+        if held { verify_vec.push(roominfo); } else { train_vec.push(roominfo); }
+    }
+    return Ok((train_vec,verify_vec));
+}
+
+// price class of every listing under the `bins` spec fitted on all of them.
+// stratification needs classes before train_vec exists; the ranges the tree
+// predicts are still fitted on the training rows alone.
+pub fn price_strata(listings: &Vec<RoomInfo>, bins: &str) -> Result<Vec<usize>,String> {
+    let ranges = PriceRanges::from_spec(bins, listings)?;
+    return Ok(listings.iter().map(|r| ranges.class_of(r.price_cents.unwrap_or(0))).collect());
+}
+
+// mark a random `ratio` of `indices` as held out
+fn hold_out(held_out: &mut Vec<bool>, mut indices: Vec<usize>, ratio: f64, rng: &mut Rng) {
+    rng.shuffle(&mut indices);
+    let count = (ratio * indices.len() as f64).round() as usize;
+    for i in indices.into_iter().take(count) { held_out[i] = true; }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use crate::tests::listings;
+
+    #[test]
+    fn grouped_split_keeps_each_host_on_one_side() {
+        let mut rows = listings(60);
+        for (i,roominfo) in rows.iter_mut().enumerate() {
+            if i % 10 != 0 { roominfo.host_id = Some(format!("host{}", i % 7)); }
+        }
+        for seed in 0..10 {
+            let (train_vec,verify_vec) = split(rows.clone(), &Split::Grouped, 0.3, seed, &Vec::new()).unwrap();
+            assert_eq!(train_vec.len() + verify_vec.len(), rows.len());
+            assert!(!train_vec.is_empty() && !verify_vec.is_empty());
+            let hosts = |side: &Vec<RoomInfo>| side.iter().filter_map(|r| r.host_id.clone()).collect::<BTreeSet<String>>();
+            assert!(hosts(&train_vec).is_disjoint(&hosts(&verify_vec)), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn grouped_split_needs_host_ids() {
+        assert!(split(listings(20), &Split::Grouped, 0.3, 1, &Vec::new()).is_err());
+        assert!(split(listings(20), &Split::Shuffled, 0.3, 1, &Vec::new()).is_ok());
+    }
+}